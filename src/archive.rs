@@ -3,31 +3,244 @@
 //! Different archive types ([`ArchiveType`]) require different handling ([`decompress`](crate::decompress))
 
 use crate::{
-    decompress::{gzip, rar, sevenzip, tar, zip},
+    config,
+    decompress::{ArchiveHandler, HANDLERS},
+    file,
+    hash::Hasher,
     tree,
 };
+use rpassword::prompt_password;
 use std::{
+    borrow::Cow,
+    fmt::Display,
+    io::{self, ErrorKind, Read},
     path::{Path, PathBuf},
+    process,
     str::FromStr,
 };
-use strum::EnumIter;
-
-#[derive(EnumIter, Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ArchiveType {
     Zip,
     SevenZip,
     Gzip,
     Tar,
     Rar,
+    Zstd,
+    Lz4,
+    Brotli,
+    Compress,
+    Xz,
+    Cpio,
+    Ar,
+    Rpm,
+    Iso,
+    Cab,
+    Ole,
+    Sfx,
+    Mail,
+    Pdf,
 }
 
 pub enum EntryData {
-    File(Vec<u8>),
+    File(FileData),
     Directory(Vec<ArchiveEntry>),
     // The expanded contents of a nested archive found during recursion (see `recurse.rs`).
     // This is distinct from `Directory` so output formatting can tell this apart from a
     // subdirectory.
     NestedArchive(Vec<ArchiveEntry>),
+    // A symbolic link and its target.  Links are not followed, as their targets may not
+    // be within the archive
+    Symlink(PathBuf),
+}
+
+// Entries are read in chunks of this size, to be hashed as they are decompressed
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// A file within an archive, hashed as it was read
+//
+// The content itself is kept (so that it can be read again, such as to recurse into it as
+// a nested archive, or to verify a package) only if it is no larger than the buffer limit
+// (see `config.rs`), so that large entries are never wholly in memory
+#[derive(Clone)]
+pub struct FileData {
+    pub hash: String,
+    pub bytes: Option<Vec<u8>>,
+    // If the content was too large to keep, the archive type it appeared to be from its
+    // start, so that we can tell that a nested archive could not be expanded
+    pub dropped_archive_type: Option<ArchiveType>,
+}
+
+impl FileData {
+    // Hash (and possibly keep) an entry's content as it is read
+    pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+        let config = config::get();
+        let mut hasher = Hasher::new(&config.hash);
+        let mut bytes = Some(Vec::new());
+        let mut dropped_archive_type = None;
+        let mut len = 0;
+
+        let mut chunk = vec![0; CHUNK_SIZE];
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            hasher.update(&chunk[..n]);
+            len += n as u64;
+
+            // Once the entry is too large to keep, we only hash the rest of it
+            if let Some(mut kept) = bytes.take() {
+                kept.extend_from_slice(&chunk[..n]);
+                if len <= config.buffer_limit {
+                    bytes = Some(kept);
+                } else {
+                    dropped_archive_type = file::archive_type_from_bytes(&kept);
+                }
+            }
+        }
+
+        Ok(FileData {
+            hash: hasher.finalize(),
+            bytes,
+            dropped_archive_type,
+        })
+    }
+
+    // Hash (and possibly keep) an entry whose content is already in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self::from_cow(Cow::Owned(bytes))
+    }
+
+    // Hash (and possibly keep) an entry whose content is a slice of a buffer already in
+    // memory (such as the archive itself), copying it only if it is kept
+    pub fn from_slice(bytes: &[u8]) -> Self {
+        Self::from_cow(Cow::Borrowed(bytes))
+    }
+
+    fn from_cow(bytes: Cow<[u8]>) -> Self {
+        let config = config::get();
+        let mut hasher = Hasher::new(&config.hash);
+        hasher.update(&bytes);
+        let hash = hasher.finalize();
+
+        if bytes.len() as u64 <= config.buffer_limit {
+            FileData {
+                hash,
+                bytes: Some(bytes.into_owned()),
+                dropped_archive_type: None,
+            }
+        } else {
+            FileData {
+                hash,
+                dropped_archive_type: file::archive_type_from_bytes(&bytes),
+                bytes: None,
+            }
+        }
+    }
+}
+
+// Where an archive being read came from: the file given on the command line, or an entry
+// of another archive (found while recursing, or the joined volumes of a split archive)
+//
+// This decides how the archive is named in password prompts and errors, and whether we
+// may prompt for its password at all
+#[derive(Clone, Copy)]
+pub enum Origin<'a> {
+    Input(&'a Path),
+    Nested {
+        context: &'a Path,
+        allow_encryption: bool,
+    },
+}
+
+impl Origin<'_> {
+    // The path of the input file, or of the nested archive within it.  Formats which do
+    // not store the name of their content name it from this
+    pub fn path(&self) -> &Path {
+        match self {
+            Origin::Input(path) => path,
+            Origin::Nested { context, .. } => context,
+        }
+    }
+
+    pub fn allows_encryption(&self) -> bool {
+        match self {
+            Origin::Input(_) => true,
+            Origin::Nested {
+                allow_encryption, ..
+            } => *allow_encryption,
+        }
+    }
+
+    fn password_prompt(&self) -> String {
+        match self {
+            Origin::Input(_) => String::from("Enter password: "),
+            Origin::Nested { context, .. } => {
+                format!("Enter password ({}): ", context.display())
+            }
+        }
+    }
+
+    // The password hook: prompt for the archive's password, if we may.  Every handler asks
+    // through this once it finds that it needs a password, so that passwords are asked for
+    // alike, wherever the archive came from
+    //
+    // Nested archives are only decrypted at the highest recursion level; otherwise, we warn
+    // that the archive is skipped, and the handler gives up on it (returning `None`)
+    pub fn password(&self) -> Option<String> {
+        if self.allows_encryption() {
+            Some(prompt_password(self.password_prompt()).unwrap())
+        } else {
+            self.skip("encrypted; use -rrr to decrypt nested archives")
+        }
+    }
+
+    // Give up on an archive whose password was incorrect
+    pub fn incorrect_password<T>(&self) -> Option<T> {
+        match self {
+            Origin::Input(_) => {
+                eprintln!("[ERROR] Incorrect password");
+                process::exit(1);
+            }
+            Origin::Nested { .. } => self.skip("incorrect password"),
+        }
+    }
+
+    // Give up on an archive we could not read, of the given kind (such as "ar archive").
+    // There is nothing else to list if the input file is unreadable, so we exit, but a
+    // nested archive is listed (and hashed) as a file instead
+    pub fn read_error<T>(&self, kind: &str, e: impl Display) -> Option<T> {
+        match self {
+            Origin::Input(_) => {
+                eprintln!("[ERROR] Failed to read {kind}: {e}");
+                process::exit(1);
+            }
+            Origin::Nested { .. } => self.skip(format_args!("failed to read {kind}: {e}")),
+        }
+    }
+
+    // Give up on a file with nothing to list (such as a PDF without embedded files).  This
+    // is only an error for the input file, as most such files nested in an archive are not
+    // meant to be containers at all
+    pub fn no_entries<T>(&self, message: &str) -> Option<T> {
+        match self {
+            Origin::Input(_) => {
+                eprintln!("[ERROR] {message}");
+                process::exit(1);
+            }
+            Origin::Nested { .. } => None,
+        }
+    }
+
+    fn skip<T>(&self, reason: impl Display) -> Option<T> {
+        eprintln!(
+            "[WARN] Skipping nested archive ({reason}): {}",
+            self.path().display()
+        );
+        None
+    }
 }
 
 pub struct ArchiveEntry {
@@ -55,63 +268,51 @@ impl ArchiveEntry {
     }
 }
 
+impl ArchiveType {
+    pub fn handler(self) -> &'static dyn ArchiveHandler {
+        *HANDLERS
+            .iter()
+            .find(|handler| handler.archive_type() == self)
+            .expect("archive type should have a handler")
+    }
+}
+
+// Identify the archive type from its MIME type
 impl FromStr for ArchiveType {
     type Err = ();
 
     fn from_str(input: &str) -> Result<ArchiveType, Self::Err> {
-        match input {
-            "application/zip" => Ok(ArchiveType::Zip),
-            "application/x-7z-compressed" => Ok(ArchiveType::SevenZip),
-            "application/gzip" => Ok(ArchiveType::Gzip),
-            "application/x-tar" => Ok(ArchiveType::Tar),
-            "application/vnd.rar" => Ok(ArchiveType::Rar),
-            _ => Err(()),
-        }
+        HANDLERS
+            .iter()
+            .find(|handler| handler.mime_types().contains(&input))
+            .map(|handler| handler.archive_type())
+            .ok_or(())
     }
 }
 
 impl From<ArchiveType> for String {
     fn from(archive_type: ArchiveType) -> Self {
-        String::from(match archive_type {
-            ArchiveType::Zip => "zip",
-            ArchiveType::SevenZip => "7zip",
-            ArchiveType::Gzip => "gzip",
-            ArchiveType::Tar => "tar",
-            ArchiveType::Rar => "rar",
-        })
+        String::from(archive_type.handler().name())
     }
 }
 
-// Returns a vector of archive entries pertaining to each file
-pub fn get_file_data_from_archive(path: &String, archive_type: ArchiveType) -> Vec<ArchiveEntry> {
-    let flat = match archive_type {
-        ArchiveType::Zip => zip::get_files_from_zip_archive(path),
-        ArchiveType::SevenZip => sevenzip::get_files_from_7z_archive(path),
-        ArchiveType::Gzip => gzip::get_files_from_gzip_or_tarball(path),
-        ArchiveType::Tar => tar::get_files_from_tar(path),
-        ArchiveType::Rar => rar::get_files_from_rar_archive(path),
-    };
-    tree::build_tree(flat)
+// An archive, once read
+pub struct Archive {
+    // The (tree-structured, and post-processed) entries of the archive
+    pub entries: Vec<ArchiveEntry>,
+    // What the archive is underneath, if it is one of many formats built on another (see
+    // `ArchiveHandler::container_kind`)
+    pub container_kind: Option<&'static str>,
 }
 
-// Returns a vector of archive entries pertaining to each file, from a buffer
-//
-// This is used for recursion into nested archives.  If we are not able to recurse, we return
-// the bytes of the file as an error value so that we can still compute its hash.
-pub fn get_file_data_from_bytes(
-    bytes: Vec<u8>,
-    archive_type: ArchiveType,
-    allow_nested_encryption: bool,
-    context: &Path,
-) -> Result<Vec<ArchiveEntry>, Vec<u8>> {
-    let flat = match archive_type {
-        ArchiveType::Zip => zip::get_files_from_zip_bytes(bytes, allow_nested_encryption, context),
-        ArchiveType::SevenZip => {
-            sevenzip::get_files_from_7z_bytes(bytes, allow_nested_encryption, context)
-        }
-        ArchiveType::Gzip => Ok(gzip::get_files_from_gzip_or_tarball_bytes(bytes, context)),
-        ArchiveType::Tar => Ok(tar::get_files_from_tar_bytes(bytes)),
-        ArchiveType::Rar => rar::get_files_from_rar_bytes(bytes, allow_nested_encryption, context),
-    }?;
-    Ok(tree::build_tree(flat))
+// Returns an archive, read by its type's handler (see `ArchiveHandler::entries`).  It is
+// labelled before it is post-processed, as post-processing may restructure its entries
+pub fn get_file_data(bytes: &[u8], archive_type: ArchiveType, origin: Origin) -> Option<Archive> {
+    let handler = archive_type.handler();
+    let entries = tree::build_tree(handler.entries(bytes, origin)?);
+    let container_kind = handler.container_kind(&entries);
+    Some(Archive {
+        entries: handler.post_process(entries, origin),
+        container_kind,
+    })
 }
@@ -0,0 +1,48 @@
+//! Global configuration
+//!
+//! Options from the command line which affect how every archive entry is read (wherever it is nested), set once at start-up.  These are used throughout the decompression modules, so are kept here rather than passed through each
+
+use crate::algo::HashAlgo;
+use std::sync::OnceLock;
+
+pub struct Config {
+    // The hashing algorithm, as entries are hashed as they are decompressed
+    pub hash: HashAlgo,
+    // The largest entry (in bytes) to keep in memory once hashed, to be read again (see
+    // [`FileData`](crate::archive::FileData))
+    pub buffer_limit: u64,
+    // List the members of multi-member gzip files separately (see
+    // [`gzip`](crate::decompress::gzip))
+    pub gzip_members: bool,
+    // Squash the layers of container images (see [`oci`](crate::decompress::oci))
+    pub squash: bool,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn init(config: Config) {
+    if CONFIG.set(config).is_err() {
+        panic!("configuration initialised twice");
+    }
+}
+
+pub fn get() -> &'static Config {
+    CONFIG.get().expect("configuration not initialised")
+}
+
+// Parse a size in bytes, optionally with a (binary) unit suffix, such as "512M"
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&s[..i], 10),
+        Some((i, 'M' | 'm')) => (&s[..i], 20),
+        Some((i, 'G' | 'g')) => (&s[..i], 30),
+        Some((i, 'T' | 't')) => (&s[..i], 40),
+        _ => (s, 0),
+    };
+    let n: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid size: {s} (expected bytes, or a K, M, G, or T suffix)"))?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("size too large: {s}"))
+}
@@ -0,0 +1,137 @@
+//! Handle ar archive format
+//!
+//! Read archive files from Unix ar archives, including the GNU and BSD variants for long file names.  Debian packages (.deb) are ar archives containing `control.tar.*` and `data.tar.*`, which are expanded by recursion (see [`recurse`](crate::recurse)).  NOTE: ar does not support encryption
+//!
+//! Format reference:
+//!   <man.freebsd.org/cgi/man.cgi?query=ar&sektion=5>
+
+use super::ArchiveHandler;
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use std::path::PathBuf;
+
+const MAGIC: &[u8] = b"!<arch>\n";
+const HEADER_LEN: usize = 60;
+const HEADER_END: &[u8] = b"`\n";
+
+// GNU variant: the long name table, and symbol tables (32- and 64-bit)
+const GNU_NAME_TABLE: &str = "//";
+const GNU_SYMBOL_TABLES: [&str; 2] = ["/", "/SYM64/"];
+
+// BSD variant: long names are stored at the start of the member data, and the symbol
+// tables (32- and 64-bit, sorted or not) have special names.  These names may themselves
+// be stored as long names (as macOS does), so are only checked for once read
+const BSD_LONG_NAME_PREFIX: &str = "#1/";
+const BSD_SYMBOL_TABLES: [&str; 4] = [
+    "__.SYMDEF",
+    "__.SYMDEF SORTED",
+    "__.SYMDEF_64",
+    "__.SYMDEF_64 SORTED",
+];
+
+fn parse_decimal(field: &[u8]) -> Result<usize, String> {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .ok_or_else(|| String::from("malformed header field"))
+}
+
+// Look up a GNU long name (`/<offset>`) in the long name table, where each name is
+// terminated by "/\n"
+fn gnu_long_name(table: &[u8], offset: usize) -> Result<String, String> {
+    let rest = table
+        .get(offset..)
+        .ok_or_else(|| format!("long name offset {offset} out of range"))?;
+    let end = rest
+        .windows(2)
+        .position(|w| w == b"/\n")
+        .or_else(|| rest.iter().position(|&b| b == b'\n'))
+        .unwrap_or(rest.len());
+    Ok(String::from_utf8_lossy(&rest[..end]).into_owned())
+}
+
+fn parse_ar(buf: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    if !buf.starts_with(MAGIC) {
+        return Err(String::from(
+            "missing ar magic (thin archives are not supported)",
+        ));
+    }
+
+    let mut files = Vec::new();
+    let mut long_names: &[u8] = &[];
+    let mut pos = MAGIC.len();
+
+    while pos + HEADER_LEN <= buf.len() {
+        let header = &buf[pos..pos + HEADER_LEN];
+        if &header[58..60] != HEADER_END {
+            return Err(format!("invalid member header at offset {pos}"));
+        }
+
+        let raw_name = String::from_utf8_lossy(&header[0..16])
+            .trim_end()
+            .to_string();
+        let size = parse_decimal(&header[48..58])?;
+
+        let data_start = pos + HEADER_LEN;
+        let data_end = data_start + size;
+        let mut data = buf
+            .get(data_start..data_end)
+            .ok_or_else(|| format!("truncated member {raw_name}"))?;
+
+        // Members are aligned to an even offset
+        pos = data_end + (data_end % 2);
+
+        let name = if raw_name == GNU_NAME_TABLE {
+            long_names = data;
+            continue;
+        } else if GNU_SYMBOL_TABLES.contains(&raw_name.as_str()) {
+            continue;
+        } else if let Some(len) = raw_name.strip_prefix(BSD_LONG_NAME_PREFIX) {
+            let len = parse_decimal(len.as_bytes())?;
+            let name = data
+                .get(..len)
+                .ok_or_else(|| String::from("truncated long name"))?;
+            data = &data[len..];
+            String::from_utf8_lossy(name)
+                .trim_end_matches('\0')
+                .to_string()
+        } else if let Some(offset) = raw_name.strip_prefix('/') {
+            gnu_long_name(long_names, parse_decimal(offset.as_bytes())?)?
+        } else {
+            // GNU terminates short names with a slash so they may contain spaces
+            raw_name.strip_suffix('/').unwrap_or(&raw_name).to_string()
+        };
+        if BSD_SYMBOL_TABLES.contains(&name.as_str()) {
+            continue;
+        }
+
+        files.push(ArchiveEntry {
+            path: Some(PathBuf::from(name)),
+            data: EntryData::File(FileData::from_slice(data)),
+        });
+    }
+
+    Ok(files)
+}
+
+pub struct Ar;
+
+impl ArchiveHandler for Ar {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Ar
+    }
+
+    fn name(&self) -> &'static str {
+        "ar"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/x-unix-archive",
+            "application/vnd.debian.binary-package",
+        ]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        parse_ar(bytes).map_or_else(|e| origin.read_error("ar archive", e), Some)
+    }
+}
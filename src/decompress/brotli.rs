@@ -0,0 +1,57 @@
+//! Handle Brotli archive format
+//!
+//! Read archive files from Brotli and tarball (.tar.br) files.  Brotli only does compression (see [`zstd`](super::zstd)), so plain and tarball content is handled together.  NOTE: Brotli does not support encryption
+//!
+//! Brotli streams have no magic bytes, so we can only identify them by their file extension (see [`file`](crate::file))
+
+use super::{ArchiveHandler, tar};
+use crate::archive::{ArchiveEntry, ArchiveType, Origin};
+use brotli_decompressor::Decompressor;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+// Internal buffer size for the Brotli decoder
+const BUFFER_SIZE: usize = 4096;
+
+// Decompress a Brotli stream, reading its content as it is decompressed
+fn get_files_from_brotli<R: Read>(
+    reader: R,
+    name: Option<PathBuf>,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let decompressor = Decompressor::new(reader, BUFFER_SIZE);
+    tar::get_files_from_tar_or_stream(decompressor, name)
+}
+
+pub struct Brotli;
+
+impl ArchiveHandler for Brotli {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Brotli
+    }
+
+    fn name(&self) -> &'static str {
+        "brotli"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // Brotli streams have no magic bytes
+    fn extensions(&self) -> &'static [&'static str] {
+        &["br"]
+    }
+
+    // Brotli streams do not store a file name, so we construct the inner file name
+    // from the archive's path
+    //
+    // As Brotli streams are only identified by their extension, a nested file named `.br`
+    // may well not be one; if so, it is listed as a file
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        get_files_from_brotli(bytes, name)
+            .map_or_else(|e| origin.read_error("Brotli stream", e), Some)
+    }
+}
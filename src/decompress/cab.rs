@@ -0,0 +1,689 @@
+//! Handle Microsoft Cabinet archive format
+//!
+//! Read archive files from Microsoft Cabinet (.cab) files.  Files within a cabinet are compressed together in "folders" (compression units, not directories); the directory hierarchy comes from the backslash-separated file names.  NOTE: cabinet files do not support encryption
+//!
+//! Stored, MSZIP, and LZX-compressed folders are supported.  Quantum compression is not supported, so files in such folders are skipped with a warning, as are files continued from or to another cabinet in a set.
+//!
+//! Format references:
+//!   <learn.microsoft.com/en-us/previous-versions/bb417343(v=msdn.10)>
+//!   <learn.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-patch> (LZX DELTA, a superset of the LZX used in cabinets)
+
+use super::ArchiveHandler;
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use miniz_oxide::inflate::{
+    TINFLStatus,
+    core::{
+        DecompressorOxide, decompress, inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+    },
+};
+use std::{ops::Range, path::PathBuf};
+
+const MAGIC: &[u8] = b"MSCF";
+
+// Header flags
+const FLAG_PREV_CABINET: u16 = 0x0001;
+const FLAG_NEXT_CABINET: u16 = 0x0002;
+const FLAG_RESERVE_PRESENT: u16 = 0x0004;
+
+// File attribute: the name is UTF-8 rather than the current code page
+const ATTRIB_NAME_IS_UTF: u16 = 0x80;
+
+// Folder indices of files that span cabinets in a set
+const FOLDER_CONTINUED_FROM_PREV: u16 = 0xfffd;
+
+// Compression types (lower four bits of the folder's compression type)
+const COMPRESS_MASK: u16 = 0x000f;
+const COMPRESS_NONE: u16 = 0;
+const COMPRESS_MSZIP: u16 = 1;
+const COMPRESS_QUANTUM: u16 = 2;
+const COMPRESS_LZX: u16 = 3;
+
+// Every MSZIP block starts with this signature, followed by a deflate stream
+const MSZIP_SIGNATURE: &[u8] = b"CK";
+
+// LZX folders give the base-2 logarithm of their window size in bits 8-12 of the
+// compression type; each window size has its own number of match position slots
+const LZX_WINDOW_BITS: Range<usize> = 15..22;
+const LZX_POSITION_SLOTS: [usize; 7] = [30, 32, 34, 36, 38, 42, 50];
+
+// LZX streams are decompressed in frames of 32 KiB, and the bit stream is realigned
+// to a 16-bit word at the end of each frame
+const LZX_FRAME_SIZE: usize = 32768;
+
+// LZX block types
+const LZX_BLOCK_VERBATIM: u32 = 1;
+const LZX_BLOCK_ALIGNED: u32 = 2;
+const LZX_BLOCK_UNCOMPRESSED: u32 = 3;
+
+// LZX Huffman tree sizes (the main tree has a literal for every byte, followed by a
+// match symbol for every combination of position slot and length header)
+const LZX_NUM_CHARS: usize = 256;
+const LZX_PRETREE_SIZE: usize = 20;
+const LZX_LENGTH_TREE_SIZE: usize = 249;
+const LZX_ALIGNED_TREE_SIZE: usize = 8;
+
+const LZX_MIN_MATCH: usize = 2;
+
+// Only the first 32768 frames of a stream have their x86 CALL instructions translated
+const LZX_E8_MAX_FRAMES: usize = 32768;
+
+struct Folder {
+    data_offset: usize,
+    n_blocks: usize,
+    compression: u16,
+    window_bits: usize,
+}
+
+struct CabFile {
+    name: String,
+    size: usize,
+    folder_offset: usize,
+    folder: u16,
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Result<u16, String> {
+    buf.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| String::from("truncated cabinet"))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<usize, String> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+        .ok_or_else(|| String::from("truncated cabinet"))
+}
+
+// Read a NUL-terminated string, returning it and the offset of the following byte
+fn read_cstr(buf: &[u8], pos: usize) -> Result<(&[u8], usize), String> {
+    let rest = buf
+        .get(pos..)
+        .ok_or_else(|| String::from("truncated cabinet"))?;
+    let len = rest
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| String::from("unterminated string"))?;
+    Ok((&rest[..len], pos + len + 1))
+}
+
+fn compression_name(compression: u16) -> &'static str {
+    match compression {
+        COMPRESS_QUANTUM => "Quantum",
+        _ => "unknown",
+    }
+}
+
+// LZX bit streams are sequences of little-endian 16-bit words, each read most
+// significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    // Peek at up to 32 bits, reading zeros past the end of the data
+    fn peek(&self, n: usize) -> u32 {
+        let byte = |i: usize| u64::from(self.data.get(i).copied().unwrap_or(0));
+        let first = self.pos / 16 * 2;
+        let bits = (0..4).fold(0, |bits, word| {
+            (bits << 16) | byte(first + word * 2 + 1) << 8 | byte(first + word * 2)
+        });
+        ((bits << (self.pos % 16)) >> (64 - n)) as u32
+    }
+
+    fn read(&mut self, n: usize) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        let value = self.peek(n);
+        self.pos += n;
+        value
+    }
+
+    // Skip to the end of the current word, if part of it has been read
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(16);
+    }
+
+    // Read bytes outside of the bit stream, which carries on with the word after them
+    fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let start = self.pos / 8;
+        let bytes = self
+            .data
+            .get(start..start + n)
+            .ok_or_else(|| String::from("truncated LZX data"))?;
+        self.data = &self.data[start + n..];
+        self.pos = 0;
+        Ok(bytes)
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.pos > self.data.len() * 8
+    }
+}
+
+// Canonical Huffman codes of up to 16 bits, given the bit length of each symbol
+struct Huffman {
+    // The (left-aligned) upper limit of the codes of each length, the first code of each
+    // length, and the position of its symbol among the symbols sorted by code
+    limit: [u32; 17],
+    first_code: [u32; 17],
+    first_pos: [u32; 17],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut count = [0u32; 17];
+        for &length in lengths {
+            count[usize::from(length.min(16))] += 1;
+        }
+        count[0] = 0;
+
+        let mut limit = [0u32; 17];
+        let mut first_code = [0u32; 17];
+        let mut first_pos = [0u32; 17];
+        let mut code = 0;
+        for i in 1..17 {
+            first_code[i] = code;
+            first_pos[i] = first_pos[i - 1] + count[i - 1];
+            code += count[i];
+            limit[i] = code << (16 - i);
+            code *= 2;
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        let mut next_pos = first_pos;
+        for (symbol, &length) in lengths.iter().enumerate() {
+            let length = usize::from(length.min(16));
+            if length != 0 {
+                symbols[next_pos[length] as usize] = symbol as u16;
+                next_pos[length] += 1;
+            }
+        }
+
+        Self {
+            limit,
+            first_code,
+            first_pos,
+            symbols,
+        }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> Result<usize, String> {
+        let code = bits.peek(16);
+        let length = (1..17)
+            .find(|&i| code < self.limit[i])
+            .ok_or_else(|| String::from("invalid LZX Huffman code"))?;
+        bits.pos += length;
+
+        let pos = self.first_pos[length] + (code >> (16 - length)) - self.first_code[length];
+        self.symbols
+            .get(pos as usize)
+            .map(|&symbol| usize::from(symbol))
+            .ok_or_else(|| String::from("invalid LZX Huffman code"))
+    }
+}
+
+// Read the bit lengths of a Huffman tree, which are coded with a pretree as changes from
+// the lengths of the previous block's tree, or as runs of zeros
+fn read_lzx_lengths(bits: &mut BitReader, lengths: &mut [u8]) -> Result<(), String> {
+    let pretree_lengths: Vec<u8> = (0..LZX_PRETREE_SIZE).map(|_| bits.read(4) as u8).collect();
+    let pretree = Huffman::new(&pretree_lengths);
+
+    let change = |previous: u8, code: usize| match code {
+        0..17 => Ok((previous + 17 - code as u8) % 17),
+        _ => Err(String::from("invalid LZX tree")),
+    };
+
+    let mut i = 0;
+    while i < lengths.len() {
+        let (run, length) = match pretree.decode(bits)? {
+            17 => (bits.read(4) as usize + 4, 0),
+            18 => (bits.read(5) as usize + 20, 0),
+            19 => {
+                let run = bits.read(1) as usize + 4;
+                (run, change(lengths[i], pretree.decode(bits)?)?)
+            }
+            code => (1, change(lengths[i], code)?),
+        };
+        let end = (i + run).min(lengths.len());
+        lengths[i..end].fill(length);
+        i = end;
+    }
+
+    Ok(())
+}
+
+// Undo the translation of the relative addresses of x86 CALL instructions (0xe8 opcodes)
+// to absolute ones, which makes repeated calls to a function compress better
+fn undo_e8_translation(frame: &mut [u8], frame_pos: usize, file_size: i32) {
+    let file_size = i64::from(file_size);
+    let mut i = 0;
+    while i + 10 < frame.len() {
+        if frame[i] != 0xe8 {
+            i += 1;
+            continue;
+        }
+
+        let pos = (frame_pos + i) as i64;
+        let operand = &mut frame[i + 1..i + 5];
+        let absolute = i64::from(i32::from_le_bytes([
+            operand[0], operand[1], operand[2], operand[3],
+        ]));
+        if absolute >= -pos && absolute < file_size {
+            let relative = if absolute >= 0 {
+                absolute - pos
+            } else {
+                absolute + file_size
+            };
+            operand.copy_from_slice(&(relative as i32).to_le_bytes());
+        }
+        i += 5;
+    }
+}
+
+// The decoder state, which carries over from one block to the next
+struct Lzx {
+    position_bases: Vec<usize>,
+    main_lengths: Vec<u8>,
+    length_lengths: [u8; LZX_LENGTH_TREE_SIZE],
+    main: Huffman,
+    length: Huffman,
+    aligned: Huffman,
+    repeated_offsets: [usize; 3],
+    block_type: u32,
+    block_length: usize,
+    block_remaining: usize,
+    e8_started: bool,
+}
+
+// The number of extra bits after a match's position slot, which give its offset from
+// the slot's base
+fn lzx_extra_bits(slot: usize) -> usize {
+    if slot < 4 {
+        0
+    } else {
+        ((slot - 2) / 2).min(17)
+    }
+}
+
+impl Lzx {
+    fn new(window_bits: usize) -> Result<Self, String> {
+        if !LZX_WINDOW_BITS.contains(&window_bits) {
+            return Err(format!("invalid LZX window size (2^{window_bits} bytes)"));
+        }
+        let position_slots = LZX_POSITION_SLOTS[window_bits - LZX_WINDOW_BITS.start];
+        let position_bases = (0..position_slots)
+            .scan(0, |base, slot| {
+                let slot_base = *base;
+                *base += 1 << lzx_extra_bits(slot);
+                Some(slot_base)
+            })
+            .collect();
+
+        Ok(Self {
+            position_bases,
+            main_lengths: vec![0; LZX_NUM_CHARS + position_slots * 8],
+            length_lengths: [0; LZX_LENGTH_TREE_SIZE],
+            main: Huffman::new(&[]),
+            length: Huffman::new(&[]),
+            aligned: Huffman::new(&[]),
+            repeated_offsets: [1; 3],
+            block_type: 0,
+            block_length: 0,
+            block_remaining: 0,
+            e8_started: false,
+        })
+    }
+
+    fn read_block_header(&mut self, bits: &mut BitReader) -> Result<(), String> {
+        // Uncompressed blocks of an odd length are followed by a byte of padding
+        if self.block_type == LZX_BLOCK_UNCOMPRESSED && self.block_length % 2 == 1 {
+            bits.read_bytes(1)?;
+        }
+
+        self.block_type = bits.read(3);
+        self.block_length = bits.read(24) as usize;
+        self.block_remaining = self.block_length;
+
+        match self.block_type {
+            LZX_BLOCK_VERBATIM | LZX_BLOCK_ALIGNED => {
+                if self.block_type == LZX_BLOCK_ALIGNED {
+                    let lengths: Vec<u8> = (0..LZX_ALIGNED_TREE_SIZE)
+                        .map(|_| bits.read(3) as u8)
+                        .collect();
+                    self.aligned = Huffman::new(&lengths);
+                }
+                read_lzx_lengths(bits, &mut self.main_lengths[..LZX_NUM_CHARS])?;
+                read_lzx_lengths(bits, &mut self.main_lengths[LZX_NUM_CHARS..])?;
+                self.main = Huffman::new(&self.main_lengths);
+                read_lzx_lengths(bits, &mut self.length_lengths)?;
+                self.length = Huffman::new(&self.length_lengths);
+
+                // Translation of CALL instructions starts with the first block that could
+                // contain one
+                self.e8_started |= self.main_lengths[0xe8] != 0;
+            }
+            LZX_BLOCK_UNCOMPRESSED => {
+                self.e8_started = true;
+
+                // The block starts at the next word (after 1-16 bits of padding), with the
+                // repeated offsets to use after it
+                bits.pos = (bits.pos / 16 + 1) * 16;
+                let offsets = bits.read_bytes(12)?;
+                for (offset, b) in self.repeated_offsets.iter_mut().zip(offsets.chunks(4)) {
+                    *offset = u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize;
+                }
+            }
+            block_type => return Err(format!("invalid LZX block type {block_type}")),
+        }
+
+        Ok(())
+    }
+
+    // Decode a literal or a match into `out` at `pos`, returning the position after it
+    fn decode_element(
+        &mut self,
+        bits: &mut BitReader,
+        out: &mut [u8],
+        pos: usize,
+    ) -> Result<usize, String> {
+        let symbol = self.main.decode(bits)?;
+        if symbol < LZX_NUM_CHARS {
+            out[pos] = symbol as u8;
+            return Ok(pos + 1);
+        }
+
+        let symbol = symbol - LZX_NUM_CHARS;
+        let mut length = symbol & 7;
+        if length == 7 {
+            length += self.length.decode(bits)?;
+        }
+        let length = length + LZX_MIN_MATCH;
+
+        let offset = match symbol >> 3 {
+            // One of the three most recent offsets, which becomes the most recent
+            slot @ 0..3 => {
+                self.repeated_offsets.swap(0, slot);
+                self.repeated_offsets[0]
+            }
+            slot => {
+                let extra = lzx_extra_bits(slot);
+                let mut offset = self.position_bases[slot] - 2;
+                // Aligned blocks code the lowest three extra bits with the aligned tree
+                if self.block_type == LZX_BLOCK_ALIGNED && extra >= 3 {
+                    offset += (bits.read(extra - 3) as usize) << 3;
+                    offset += self.aligned.decode(bits)?;
+                } else {
+                    offset += bits.read(extra) as usize;
+                }
+                self.repeated_offsets =
+                    [offset, self.repeated_offsets[0], self.repeated_offsets[1]];
+                offset
+            }
+        };
+
+        if offset == 0 || offset > pos {
+            return Err(String::from("invalid LZX match offset"));
+        }
+        if pos + length > out.len() {
+            return Err(String::from("LZX match past the end of its block"));
+        }
+        for i in pos..pos + length {
+            out[i] = out[i - offset];
+        }
+
+        Ok(pos + length)
+    }
+
+    fn decompress(mut self, data: &[u8], total: usize) -> Result<Vec<u8>, String> {
+        let mut out = vec![0; total];
+        let mut bits = BitReader::new(data);
+
+        // The stream starts with whether CALL instructions are translated, and if so, the
+        // file size to translate them with
+        let e8_file_size = match bits.read(1) {
+            1 => bits.read(32) as i32,
+            _ => 0,
+        };
+        let mut e8_frames = Vec::new();
+
+        for (frame, start) in (0..total).step_by(LZX_FRAME_SIZE).enumerate() {
+            let end = total.min(start + LZX_FRAME_SIZE);
+            let mut pos = start;
+            while pos < end {
+                if self.block_remaining == 0 {
+                    self.read_block_header(&mut bits)?;
+                }
+                let run_end = end.min(pos + self.block_remaining);
+                self.block_remaining -= run_end - pos;
+
+                if self.block_type == LZX_BLOCK_UNCOMPRESSED {
+                    out[pos..run_end].copy_from_slice(bits.read_bytes(run_end - pos)?);
+                    pos = run_end;
+                } else {
+                    while pos < run_end {
+                        pos = self.decode_element(&mut bits, &mut out[..run_end], pos)?;
+                    }
+                }
+            }
+
+            if bits.is_exhausted() {
+                return Err(String::from("truncated LZX data"));
+            }
+            bits.align();
+
+            if self.e8_started && e8_file_size != 0 && frame < LZX_E8_MAX_FRAMES {
+                e8_frames.push(start..end);
+            }
+        }
+
+        // The translation applies only to the output, not to the window that matches
+        // refer back to, so it is undone once everything is decompressed
+        for frame in e8_frames {
+            let frame_pos = frame.start;
+            undo_e8_translation(&mut out[frame], frame_pos, e8_file_size);
+        }
+
+        Ok(out)
+    }
+}
+
+// Decompress all of the data blocks of a folder into one buffer
+//
+// MSZIP blocks are each a complete deflate stream, but may refer back to the data of
+// previous blocks, so every block is decompressed into the same output buffer
+fn decompress_folder(buf: &[u8], folder: &Folder, block_reserve: usize) -> Result<Vec<u8>, String> {
+    // Find every block's data and uncompressed size first, so that we know how large
+    // the output needs to be
+    let mut blocks = Vec::with_capacity(folder.n_blocks);
+    let mut pos = folder.data_offset;
+    for _ in 0..folder.n_blocks {
+        let compressed_len = read_u16(buf, pos + 4)? as usize;
+        let uncompressed_len = read_u16(buf, pos + 6)? as usize;
+        let start = pos + 8 + block_reserve;
+        let data = buf
+            .get(start..start + compressed_len)
+            .ok_or_else(|| String::from("truncated data block"))?;
+        blocks.push((data, uncompressed_len));
+        pos = start + compressed_len;
+    }
+
+    let total = blocks.iter().map(|(_, len)| len).sum();
+
+    // LZX frames don't line up with data blocks, so the blocks are one continuous stream
+    if folder.compression == COMPRESS_LZX {
+        let stream: Vec<u8> = blocks.iter().flat_map(|(data, _)| *data).copied().collect();
+        return Lzx::new(folder.window_bits)?.decompress(&stream, total);
+    }
+
+    let mut out = vec![0; total];
+    let mut out_pos = 0;
+
+    for (data, uncompressed_len) in blocks {
+        match folder.compression {
+            COMPRESS_NONE => {
+                if data.len() != uncompressed_len {
+                    return Err(String::from("stored data block size mismatch"));
+                }
+                out[out_pos..out_pos + data.len()].copy_from_slice(data);
+            }
+            COMPRESS_MSZIP => {
+                let stream = data
+                    .strip_prefix(MSZIP_SIGNATURE)
+                    .ok_or_else(|| String::from("missing MSZIP block signature"))?;
+                let mut decompressor = DecompressorOxide::new();
+                let (status, _, written) = decompress(
+                    &mut decompressor,
+                    stream,
+                    &mut out[..out_pos + uncompressed_len],
+                    out_pos,
+                    TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+                );
+                if status != TINFLStatus::Done || written != uncompressed_len {
+                    return Err(format!("MSZIP decompression failed ({status:?})"));
+                }
+            }
+            _ => unreachable!("unsupported folders are not decompressed"),
+        }
+        out_pos += uncompressed_len;
+    }
+
+    Ok(out)
+}
+
+fn parse_cab(buf: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    if !buf.starts_with(MAGIC) {
+        return Err(String::from("missing cabinet magic"));
+    }
+
+    let files_offset = read_u32(buf, 16)?;
+    let n_folders = read_u16(buf, 26)? as usize;
+    let n_files = read_u16(buf, 28)? as usize;
+    let flags = read_u16(buf, 30)?;
+
+    // Optional per-cabinet, per-folder, and per-block reserved areas
+    let mut pos = 36;
+    let (folder_reserve, block_reserve) = if flags & FLAG_RESERVE_PRESENT != 0 {
+        let header_reserve = read_u16(buf, pos)? as usize;
+        let folder_reserve = *buf.get(pos + 2).ok_or("truncated cabinet")? as usize;
+        let block_reserve = *buf.get(pos + 3).ok_or("truncated cabinet")? as usize;
+        pos += 4 + header_reserve;
+        (folder_reserve, block_reserve)
+    } else {
+        (0, 0)
+    };
+
+    // Skip the names of the previous and next cabinets (and their disks) in the set
+    let n_strings = [FLAG_PREV_CABINET, FLAG_NEXT_CABINET]
+        .iter()
+        .filter(|&&f| flags & f != 0)
+        .count()
+        * 2;
+    for _ in 0..n_strings {
+        pos = read_cstr(buf, pos)?.1;
+    }
+
+    let folders = (0..n_folders)
+        .map(|i| {
+            let folder = pos + i * (8 + folder_reserve);
+            let compression = read_u16(buf, folder + 6)?;
+            Ok(Folder {
+                data_offset: read_u32(buf, folder)?,
+                n_blocks: read_u16(buf, folder + 4)? as usize,
+                compression: compression & COMPRESS_MASK,
+                window_bits: usize::from((compression >> 8) & 0x1f),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut pos = files_offset;
+    let mut cab_files = Vec::with_capacity(n_files);
+    for _ in 0..n_files {
+        let attributes = read_u16(buf, pos + 14)?;
+        let (name, next) = read_cstr(buf, pos + 16)?;
+
+        // Names are in the current code page unless flagged as UTF-8; either way, we
+        // can only do our best to decode them
+        let name = if attributes & ATTRIB_NAME_IS_UTF != 0 {
+            String::from_utf8_lossy(name).into_owned()
+        } else {
+            name.iter().map(|&b| b as char).collect()
+        };
+
+        cab_files.push(CabFile {
+            name: name.replace('\\', "/"),
+            size: read_u32(buf, pos)?,
+            folder_offset: read_u32(buf, pos + 4)?,
+            folder: read_u16(buf, pos + 8)?,
+        });
+        pos = next;
+    }
+
+    // Decompress each folder at most once, and only when a file needs it
+    let mut decompressed: Vec<Option<Vec<u8>>> = (0..folders.len()).map(|_| None).collect();
+    let mut files = Vec::with_capacity(cab_files.len());
+
+    for file in cab_files {
+        let Some(folder) = folders.get(file.folder as usize) else {
+            let reason = if file.folder >= FOLDER_CONTINUED_FROM_PREV {
+                "continued across cabinets in a set"
+            } else {
+                "invalid folder"
+            };
+            eprintln!("[WARN] Skipping {} ({reason})", file.name);
+            continue;
+        };
+
+        if !matches!(
+            folder.compression,
+            COMPRESS_NONE | COMPRESS_MSZIP | COMPRESS_LZX
+        ) {
+            eprintln!(
+                "[WARN] Skipping {} ({} compression is not supported)",
+                file.name,
+                compression_name(folder.compression)
+            );
+            continue;
+        }
+
+        let data = match &mut decompressed[file.folder as usize] {
+            Some(data) => data,
+            slot => slot.insert(decompress_folder(buf, folder, block_reserve)?),
+        };
+        let bytes = data
+            .get(file.folder_offset..file.folder_offset + file.size)
+            .ok_or_else(|| format!("data for {} out of range", file.name))?;
+
+        files.push(ArchiveEntry {
+            path: Some(PathBuf::from(file.name)),
+            data: EntryData::File(FileData::from_slice(bytes)),
+        });
+    }
+
+    Ok(files)
+}
+
+pub struct Cab;
+
+impl ArchiveHandler for Cab {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Cab
+    }
+
+    fn name(&self) -> &'static str {
+        "cab"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/vnd.ms-cab-compressed"]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        parse_cab(bytes).map_or_else(|e| origin.read_error("cabinet file", e), Some)
+    }
+}
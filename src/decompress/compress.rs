@@ -0,0 +1,171 @@
+//! Handle Unix compress archive format
+//!
+//! Read archive files from LZW-compressed (.Z) and tarball (.tar.Z) files, as produced by the classic Unix `compress` utility.  Like [`gzip`](super::gzip), this only does compression, so plain and tarball content is handled together.  NOTE: compress does not support encryption
+//!
+//! There is no maintained crate for this format, so the decoder is implemented here, following the reference implementation in ncompress:
+//!   <github.com/vapier/ncompress/blob/v5.0/compress.c>
+
+use super::{ArchiveHandler, tar};
+use crate::archive::{ArchiveEntry, ArchiveType, Origin};
+use std::path::PathBuf;
+
+const MAGIC: [u8; 2] = [0x1f, 0x9d];
+
+// Header flags (third byte): the lower five bits give the maximum code width, and the
+// high bit says whether the CLEAR code is in use ("block mode")
+const BIT_MASK: u8 = 0x1f;
+const BLOCK_MODE: u8 = 0x80;
+
+const INIT_BITS: usize = 9;
+const MIN_MAX_BITS: usize = 9;
+const MAX_MAX_BITS: usize = 16;
+const CLEAR: usize = 256;
+
+// Read a code of `n_bits` bits, least significant bit first, at bit offset `pos`
+fn read_code(input: &[u8], pos: usize, n_bits: usize) -> usize {
+    let i = pos >> 3;
+    let byte = |j: usize| *input.get(i + j).unwrap_or(&0) as usize;
+    let word = byte(0) | (byte(1) << 8) | (byte(2) << 16);
+    (word >> (pos & 7)) & ((1 << n_bits) - 1)
+}
+
+// Codes are written in groups of eight, so each group is `n_bits` bytes long.  When the
+// code width changes (or the table is cleared), the remainder of the current group is
+// padding, and the next code starts at the following group boundary
+fn align_to_group(pos: usize, n_bits: usize) -> usize {
+    pos.next_multiple_of(n_bits << 3)
+}
+
+fn decompress_lzw(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 3 || data[..2] != MAGIC {
+        return Err(String::from("not a compress (.Z) stream"));
+    }
+
+    let max_bits = (data[2] & BIT_MASK) as usize;
+    let block_mode = data[2] & BLOCK_MODE != 0;
+    if !(MIN_MAX_BITS..=MAX_MAX_BITS).contains(&max_bits) {
+        return Err(format!("unsupported maximum code width {max_bits}"));
+    }
+
+    let input = &data[3..];
+    let input_bits = input.len() << 3;
+    let max_max_code = 1 << max_bits;
+
+    // The first 256 codes are literal bytes
+    let mut prefix = vec![0usize; max_max_code];
+    let mut suffix: Vec<u8> = (0..max_max_code).map(|c| c as u8).collect();
+
+    let mut n_bits = INIT_BITS;
+    let mut max_code = (1 << n_bits) - 1;
+    let mut free_ent = if block_mode { CLEAR + 1 } else { CLEAR };
+
+    // Group alignment is relative to where the current code width started
+    let mut base = 0;
+    let mut pos = 0;
+
+    let mut old_code: Option<usize> = None;
+    let mut fin_char = 0;
+    let mut stack = Vec::new();
+    let mut bytes = Vec::new();
+
+    loop {
+        if free_ent > max_code {
+            base += align_to_group(pos, n_bits);
+            pos = 0;
+            n_bits += 1;
+            max_code = if n_bits == max_bits {
+                max_max_code
+            } else {
+                (1 << n_bits) - 1
+            };
+        }
+
+        if base + pos + n_bits > input_bits {
+            break;
+        }
+        let code = read_code(input, base + pos, n_bits);
+        pos += n_bits;
+
+        // The first code is always a literal
+        let Some(old) = old_code else {
+            if code >= CLEAR {
+                return Err(String::from("corrupt input"));
+            }
+            fin_char = code as u8;
+            bytes.push(fin_char);
+            old_code = Some(code);
+            continue;
+        };
+
+        if code == CLEAR && block_mode {
+            // The next entry added is a placeholder which the encoder never references,
+            // so the table effectively restarts at `CLEAR + 1`
+            free_ent = CLEAR;
+            base += align_to_group(pos, n_bits);
+            pos = 0;
+            n_bits = INIT_BITS;
+            max_code = (1 << n_bits) - 1;
+            continue;
+        }
+
+        let in_code = code;
+        let mut code = code;
+
+        // Special case: the code is the one we are about to define (KwKwK)
+        if code >= free_ent {
+            if code > free_ent {
+                return Err(String::from("corrupt input"));
+            }
+            stack.push(fin_char);
+            code = old;
+        }
+
+        while code > u8::MAX as usize {
+            stack.push(suffix[code]);
+            code = prefix[code];
+        }
+        fin_char = suffix[code];
+        stack.push(fin_char);
+        bytes.extend(stack.drain(..).rev());
+
+        if free_ent < max_max_code {
+            prefix[free_ent] = old;
+            suffix[free_ent] = fin_char;
+            free_ent += 1;
+        }
+
+        old_code = Some(in_code);
+    }
+
+    Ok(bytes)
+}
+
+// The LZW decoder works on the whole stream at once, so the content is read from memory
+fn get_files_from_lzw(data: &[u8], name: Option<PathBuf>) -> Result<Vec<ArchiveEntry>, String> {
+    let bytes = decompress_lzw(data)?;
+    tar::get_files_from_tar_or_stream(bytes.as_slice(), name).map_err(|e| e.to_string())
+}
+
+pub struct Compress;
+
+impl ArchiveHandler for Compress {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Compress
+    }
+
+    fn name(&self) -> &'static str {
+        "compress"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-compress"]
+    }
+
+    // The .Z format does not store a file name, so we construct the inner file name
+    // from the archive's path
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        get_files_from_lzw(bytes, name)
+            .map_or_else(|e| origin.read_error("compress (.Z) stream", e), Some)
+    }
+}
@@ -0,0 +1,342 @@
+//! Handle cpio archive format
+//!
+//! Read archive files from cpio archives, in the portable ASCII (newc and its crc variant; odc) and old binary (either byte order) formats, as well as the stripped variant of RPM payloads.  Initramfs images and RPM payloads are cpio archives, usually compressed with gzip, xz, or zstd; those layers are handled by recursion (see [`recurse`](crate::recurse)).  NOTE: cpio does not support encryption
+//!
+//! Format reference:
+//!   <man.freebsd.org/cgi/man.cgi?query=cpio&sektion=5>
+
+use super::{ArchiveHandler, Matcher};
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+const NEWC_MAGIC: &[u8] = b"070701";
+const CRC_MAGIC: &[u8] = b"070702";
+const ODC_MAGIC: &[u8] = b"070707";
+const BINARY_MAGIC: u16 = 0o070707;
+
+// RPM payloads with files of 4 GiB or more (which the newc format cannot record the size
+// of) strip each header down to the magic and the file's index in the package header,
+// which records everything else:
+//   <github.com/rpm-software-management/rpm/blob/rpm-4.19.0-release/lib/cpio.c>
+const STRIPPED_MAGIC: &[u8] = b"07070X";
+
+const NEWC_HEADER_LEN: usize = 110;
+const ODC_HEADER_LEN: usize = 76;
+const BINARY_HEADER_LEN: usize = 26;
+const STRIPPED_HEADER_LEN: usize = 14;
+
+const TRAILER: &str = "TRAILER!!!";
+
+// File type bits of the mode field
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+// Returns whether the buffer starts with one of the ASCII cpio headers
+//
+// `infer` only recognises the newc and binary formats, so this is used to detect the
+// others (see [`file`](crate::file))
+pub fn is_ascii_cpio(buf: &[u8]) -> bool {
+    [NEWC_MAGIC, CRC_MAGIC, ODC_MAGIC, STRIPPED_MAGIC]
+        .iter()
+        .any(|magic| buf.starts_with(magic))
+}
+
+enum Format {
+    // newc and crc formats only differ in the checksum field, which we do not verify
+    Newc,
+    Odc,
+    Binary { little_endian: bool },
+    Stripped,
+}
+
+// The metadata of a file, as recorded in an RPM package's header, for payloads with
+// stripped headers
+pub struct StrippedFile {
+    pub name: String,
+    pub dev: u64,
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u64,
+    pub size: usize,
+}
+
+struct Header {
+    // Only stripped headers give the name here, rather than after the header
+    name: Option<String>,
+    dev: u64,
+    ino: u64,
+    mode: u32,
+    nlink: u64,
+    name_size: usize,
+    file_size: usize,
+}
+
+fn parse_number(field: &[u8], radix: u32) -> Result<u64, String> {
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|s| u64::from_str_radix(s, radix).ok())
+        .ok_or_else(|| String::from("malformed header field"))
+}
+
+fn detect_format(buf: &[u8]) -> Option<Format> {
+    if buf.starts_with(NEWC_MAGIC) || buf.starts_with(CRC_MAGIC) {
+        Some(Format::Newc)
+    } else if buf.starts_with(ODC_MAGIC) {
+        Some(Format::Odc)
+    } else if buf.starts_with(STRIPPED_MAGIC) {
+        Some(Format::Stripped)
+    } else if buf.len() >= 2 && u16::from_le_bytes([buf[0], buf[1]]) == BINARY_MAGIC {
+        Some(Format::Binary {
+            little_endian: true,
+        })
+    } else if buf.len() >= 2 && u16::from_be_bytes([buf[0], buf[1]]) == BINARY_MAGIC {
+        Some(Format::Binary {
+            little_endian: false,
+        })
+    } else {
+        None
+    }
+}
+
+impl Format {
+    fn header_len(&self) -> usize {
+        match self {
+            Format::Newc => NEWC_HEADER_LEN,
+            Format::Odc => ODC_HEADER_LEN,
+            Format::Binary { .. } => BINARY_HEADER_LEN,
+            Format::Stripped => STRIPPED_HEADER_LEN,
+        }
+    }
+
+    // Both the name and the file data are padded to this alignment, relative to the
+    // start of the header
+    fn alignment(&self) -> usize {
+        match self {
+            Format::Newc | Format::Stripped => 4,
+            Format::Odc => 1,
+            Format::Binary { .. } => 2,
+        }
+    }
+
+    // Stripped headers have no name, which is instead looked up with the rest of the
+    // file's metadata
+    fn parse_header(&self, buf: &[u8], stripped: &[StrippedFile]) -> Result<Header, String> {
+        match self {
+            Format::Newc => {
+                // Thirteen eight-character hexadecimal fields follow the magic
+                let field = |i: usize| parse_number(&buf[6 + i * 8..6 + (i + 1) * 8], 16);
+                Ok(Header {
+                    name: None,
+                    ino: field(0)?,
+                    mode: field(1)? as u32,
+                    nlink: field(4)?,
+                    file_size: field(6)? as usize,
+                    dev: (field(7)? << 32) | field(8)?,
+                    name_size: field(11)? as usize,
+                })
+            }
+            Format::Odc => {
+                let field = |start: usize, len: usize| parse_number(&buf[start..start + len], 8);
+                Ok(Header {
+                    name: None,
+                    dev: field(6, 6)?,
+                    ino: field(12, 6)?,
+                    mode: field(18, 6)? as u32,
+                    nlink: field(36, 6)?,
+                    name_size: field(59, 6)? as usize,
+                    file_size: field(65, 11)? as usize,
+                })
+            }
+            Format::Binary { little_endian } => {
+                let word = |i: usize| {
+                    let bytes = [buf[i * 2], buf[i * 2 + 1]];
+                    if *little_endian {
+                        u16::from_le_bytes(bytes)
+                    } else {
+                        u16::from_be_bytes(bytes)
+                    }
+                };
+                // Four-byte values are stored as two words, most significant first
+                let long = |i: usize| ((word(i) as usize) << 16) | word(i + 1) as usize;
+                Ok(Header {
+                    name: None,
+                    dev: word(1) as u64,
+                    ino: word(2) as u64,
+                    mode: word(3) as u32,
+                    nlink: word(6) as u64,
+                    name_size: word(10) as usize,
+                    file_size: long(11),
+                })
+            }
+            Format::Stripped => {
+                let index = parse_number(&buf[6..14], 16)? as usize;
+                let file = stripped.get(index).ok_or_else(|| {
+                    format!("stripped header for file {index}, whose metadata is only in its RPM package")
+                })?;
+                Ok(Header {
+                    name: Some(file.name.clone()),
+                    dev: file.dev,
+                    ino: file.ino,
+                    mode: file.mode,
+                    nlink: file.nlink,
+                    name_size: 0,
+                    file_size: file.size,
+                })
+            }
+        }
+    }
+}
+
+// Entry names are usually relative to the archive root ("./usr/bin"), but may be
+// absolute; either way we only keep the normal components
+fn normalise_path(name: &str) -> PathBuf {
+    Path::new(name)
+        .components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+// Stripped headers (from RPM payloads) are read with the metadata of the package's files,
+// in the order the package's header lists them
+//
+// The content of each regular file is also given to `on_content` (with its path), whether
+// or not it is small enough to keep (see `FileData`), so that callers can check it against
+// digests of their own.  Earlier hard links are given it again once their data is found
+pub fn parse_cpio<'a>(
+    buf: &'a [u8],
+    stripped: &[StrippedFile],
+    mut on_content: impl FnMut(&Path, &'a [u8]),
+) -> Result<Vec<ArchiveEntry>, String> {
+    let mut files = Vec::new();
+
+    // Hard links in the newc format only store their data with the last link, so we
+    // record the entries of each inode to fill in the data of the earlier links
+    let mut links: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+
+    let mut pos = 0;
+    while pos < buf.len() {
+        let rest = &buf[pos..];
+        let format =
+            detect_format(rest).ok_or_else(|| format!("invalid header at offset {pos}"))?;
+        let align = format.alignment();
+
+        if rest.len() < format.header_len() {
+            return Err(String::from("truncated header"));
+        }
+        let mut header = format.parse_header(rest, stripped)?;
+
+        // The name includes a trailing NUL byte.  Stripped headers have none, and their data
+        // follows the header directly
+        let (name, data_start) = if let Some(name) = header.name.take() {
+            (name, pos + format.header_len())
+        } else {
+            let name_start = pos + format.header_len();
+            let name_end = name_start + header.name_size;
+            let name = buf
+                .get(name_start..name_end)
+                .ok_or_else(|| String::from("truncated entry name"))?;
+            let name =
+                String::from_utf8_lossy(name.strip_suffix(&[0]).unwrap_or(name)).into_owned();
+            (name, name_end.next_multiple_of(align))
+        };
+        let data_end = data_start + header.file_size;
+        let data = buf
+            .get(data_start..data_end)
+            .ok_or_else(|| format!("truncated data for {name}"))?;
+        pos = data_end.next_multiple_of(align).min(buf.len());
+
+        if name == TRAILER {
+            // Inode numbers are only unique within an archive, so those of any archive
+            // concatenated after this one are not links to these
+            links.clear();
+
+            // Archives are often padded with NUL bytes to a block boundary, and may be
+            // followed by another archive.  Initramfs images in particular are commonly
+            // an uncompressed cpio archive (e.g., CPU microcode) followed by a compressed
+            // one, so we keep any trailing data as a file so that it can be recursed into
+            while pos < buf.len() && buf[pos] == 0 {
+                pos += 1;
+            }
+            if pos < buf.len() && detect_format(&buf[pos..]).is_none() {
+                files.push(ArchiveEntry {
+                    path: Some(PathBuf::from(format!("(appended data at {pos:#x})"))),
+                    data: EntryData::File(FileData::from_slice(&buf[pos..])),
+                });
+                break;
+            }
+            continue;
+        }
+
+        let path = normalise_path(&name);
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+
+        let data = match header.mode & S_IFMT {
+            S_IFDIR => EntryData::Directory(Vec::new()),
+            S_IFLNK => {
+                EntryData::Symlink(PathBuf::from(String::from_utf8_lossy(data).into_owned()))
+            }
+            S_IFREG => {
+                let file = FileData::from_slice(data);
+                if header.nlink > 1 {
+                    let indices = links.entry((header.dev, header.ino)).or_default();
+                    if !data.is_empty() {
+                        for &i in indices.iter() {
+                            files[i].data = EntryData::File(file.clone());
+                            if let Some(link) = &files[i].path {
+                                on_content(link, data);
+                            }
+                        }
+                    }
+                    indices.push(files.len());
+                }
+                on_content(&path, data);
+                EntryData::File(file)
+            }
+            // Device nodes, FIFOs, and sockets have no content to hash
+            _ => continue,
+        };
+
+        files.push(ArchiveEntry {
+            path: Some(path),
+            data,
+        });
+    }
+
+    Ok(files)
+}
+
+pub struct Cpio;
+
+impl ArchiveHandler for Cpio {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Cpio
+    }
+
+    fn name(&self) -> &'static str {
+        "cpio"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-cpio"]
+    }
+
+    fn matchers(&self) -> &'static [Matcher] {
+        &[Matcher {
+            mime_type: "application/x-cpio",
+            extension: "cpio",
+            matches: is_ascii_cpio,
+        }]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        parse_cpio(bytes, &[], |_, _| {})
+            .map_or_else(|e| origin.read_error("cpio archive", e), Some)
+    }
+}
@@ -1,125 +1,261 @@
 //! Handle gzip archive format
 //!
-//! Read archive files from gzip and tarball (.tar.gz) files.  Both types have the same MIME type, so we handle these together (though, see [`tar`](super::tar)).  NOTE: gzip does not support encryption
+//! Read archive files from gzip and tarball (.tar.gz) files.  Both types have the same MIME type, so we handle these together (though, see [`tar`](super::tar)).  The inner file is named from the gzip header, where the compressor recorded it (`gzip` does by default).  Gzip files may also be made of several concatenated members (such as from `cat a.gz b.gz`, or from parallel compressors), which are read as one stream, or listed separately (see [`config`](crate::config)).  NOTE: gzip does not support encryption
+//!
+//! Gzip file format specification:
+//!   <www.rfc-editor.org/rfc/rfc1952>
 
-use super::tar::ReadTarArchive;
-use crate::archive::{ArchiveEntry, EntryData};
-use flate2::read::GzDecoder;
+use super::{ArchiveHandler, oci, package, tar};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin},
+    config,
+};
+use flate2::{GzHeader, bufread};
 use std::{
-    fs::File,
-    io::{Cursor, Read},
+    io::{self, BufRead, Read},
+    mem,
     path::{Path, PathBuf},
 };
-use tar::Archive;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 
-fn to_gz_decoder(path: &String) -> GzDecoder<File> {
-    let file = File::open(path).unwrap();
-    GzDecoder::new(file)
+// The header and (decompressed) length of a single gzip stream
+struct Member {
+    header: Option<GzHeader>,
+    len: usize,
 }
 
-fn to_archive(path: &String) -> Archive<GzDecoder<File>> {
-    Archive::new(to_gz_decoder(path))
+// Decompress each member of a (possibly multi-member) gzip file in turn, as one stream,
+// keeping note of where each member starts
+//
+// Each member is a complete gzip stream (header, data, and trailer), so we decode members
+// back to back until the input runs out
+struct MemberReader<R> {
+    gzd: Option<bufread::GzDecoder<R>>,
+    members: Vec<Member>,
+    len: usize,
+    // Whether to stop at the end of each member (to be read on from the next), so that
+    // members can be read separately
+    split: bool,
 }
 
-fn count_archive_files(path: &String) -> usize {
-    let mut archive = to_archive(path);
-    if let Ok(entries) = archive.entries() {
-        entries.filter_map(|e| e.ok()).count()
-    } else {
-        0
+impl<R: BufRead> MemberReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            gzd: Some(bufread::GzDecoder::new(reader)),
+            members: Vec::new(),
+            len: 0,
+            split: false,
+        }
     }
 }
 
-fn count_archive_entries(path: &String) -> usize {
-    let mut archive = to_archive(path);
-    if let Ok(entries) = archive.entries() {
-        entries.count()
-    } else {
-        0
+impl<R: BufRead> Read for MemberReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(gzd) = &mut self.gzd else {
+                return Ok(0);
+            };
+
+            match gzd.read(buf) {
+                Ok(0) if !buf.is_empty() => {}
+                Ok(n) => {
+                    self.len += n;
+                    return Ok(n);
+                }
+                // Like `gzip -d`, we ignore trailing garbage (such as padding) after the
+                // last member
+                Err(e) if !self.members.is_empty() && self.len == 0 => {
+                    eprintln!(
+                        "[WARN] Ignoring trailing data after gzip member {}: {e}",
+                        self.members.len()
+                    );
+                    self.gzd = None;
+                    return Ok(0);
+                }
+                Err(e) => return Err(e),
+            }
+
+            // The member has ended, so we move on to the next, if there is one
+            let gzd = self.gzd.take().unwrap();
+            self.members.push(Member {
+                header: gzd.header().cloned(),
+                len: mem::take(&mut self.len),
+            });
+            let mut reader = gzd.into_inner();
+            if !reader.fill_buf()?.is_empty() {
+                self.gzd = Some(bufread::GzDecoder::new(reader));
+            }
+            if self.split {
+                return Ok(0);
+            }
+        }
     }
 }
 
-// A file is an archive (.tar) file if it contains at least one valid entry
-// The file is necessarily compressed (.gz) if we got to this function from archive.rs
-fn is_tar_gz(path: &String) -> bool {
-    !(count_archive_files(path) == 0 || count_archive_entries(path) == 0)
+// The original file name, if the compressor stored it in the header (FNAME)
+fn member_name(member: &Member) -> Option<PathBuf> {
+    let name = member.header.as_ref()?.filename()?;
+
+    // The specification says this is Latin-1, but tools usually write the name as it was
+    // on disc, which is now mostly UTF-8
+    let name = String::from_utf8(name.to_vec())
+        .unwrap_or_else(|_| name.iter().map(|&b| char::from(b)).collect());
+
+    // The name is meant to have its directory stripped already; we make sure of that, so
+    // that the header cannot place the file elsewhere in the listing
+    Path::new(&name).file_name().map(PathBuf::from)
 }
 
-pub fn get_files_from_gzip_or_tarball(path: &String) -> Vec<ArchiveEntry> {
-    if is_tar_gz(path) {
-        to_archive(path).get_files_from_tar()
-    } else {
-        let mut gzd = to_gz_decoder(path);
-
-        // We have to construct the file name based on given path because it has no
-        // associated metadata in the gzip format
-        let name = Path::new(path).file_stem().map(PathBuf::from);
-        let mut bytes = Vec::new();
-        gzd.read_to_end(&mut bytes).unwrap();
-
-        // Gzip format has no support for multiple files, because it's only doing
-        // compression, not archiving/containerising.  NB: as a result of this, gzip
-        // by itself does not know anything about file structure, which is why we
-        // have to construct the inner file based on the given path
-        vec![ArchiveEntry {
-            path: name,
-            data: EntryData::File(bytes),
-        }]
+// The gzip format has no other metadata of note, so we report the modification time
+// (MTIME) and comment (FCOMMENT) from the header where present.  This is only reported
+// for the input file, as that of every nested gzip file would drown out the listing
+fn print_member_info(member: &Member, name: Option<&Path>) {
+    let Some(header) = &member.header else {
+        return;
+    };
+    let name = name.map(Path::display);
+    let name = name.as_ref().map_or(String::new(), |n| format!(" ({n})"));
+
+    // A time of zero means that no time was stored
+    if header.mtime() != 0
+        && let Ok(mtime) = OffsetDateTime::from_unix_timestamp(header.mtime().into())
+        && let Ok(mtime) = mtime.format(&Rfc3339)
+    {
+        eprintln!("[INFO] Gzip modification time: {mtime}{name}");
     }
+
+    if let Some(comment) = header.comment() {
+        let comment = String::from_utf8_lossy(comment);
+        eprintln!("[INFO] Gzip comment: {comment}{name}");
+    }
+}
+
+// List each member of the (decompressed) stream as a file of its own
+fn get_files_from_members(
+    files: Vec<FileData>,
+    members: Vec<Member>,
+    fallback: Option<PathBuf>,
+    print_info: bool,
+) -> Vec<ArchiveEntry> {
+    members
+        .iter()
+        .zip(files)
+        .enumerate()
+        .map(|(i, (member, file))| {
+            let name = member_name(member).or_else(|| match members.len() {
+                1 => fallback.clone(),
+                _ => {
+                    let stem = fallback.as_deref().unwrap_or(Path::new("")).display();
+                    Some(PathBuf::from(format!("{stem} (member {})", i + 1)))
+                }
+            });
+            if print_info {
+                print_member_info(member, name.as_deref());
+            }
+            ArchiveEntry {
+                path: name,
+                data: EntryData::File(file),
+            }
+        })
+        .collect()
 }
 
-// Utilities on bytes buffer
+// Decompress the gzip file in a single pass.  Both tarballs and plain content have the same
+// MIME type, so we tell them apart from the first (tar header) block of the stream: a
+// tarball is read entry by entry as it is decompressed, and anything else is hashed as it
+// is decompressed
 //
-// This is used for recursing into nested archives
+// Gzip format has no support for multiple files, because it's only doing compression, not
+// archiving/containerising.  NB: as a result of this, gzip by itself does not know anything
+// about file structure, so if the header has no name for the inner file, we have to
+// construct one based on the given path (`fallback`)
+fn get_files_from_gzip<R: BufRead>(
+    reader: R,
+    fallback: Option<PathBuf>,
+    print_info: bool,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut stream = MemberReader::new(reader);
+    let mut head = Vec::with_capacity(512);
+    stream.by_ref().take(512).read_to_end(&mut head)?;
 
-fn to_gz_decoder_from_bytes(bytes: &[u8]) -> GzDecoder<Cursor<&[u8]>> {
-    GzDecoder::new(Cursor::new(bytes))
-}
+    if tar::is_tar_header(&head) {
+        return tar::get_files_from_tar_stream(head.chain(stream));
+    }
 
-fn to_archive_from_bytes(bytes: &[u8]) -> Archive<GzDecoder<Cursor<&[u8]>>> {
-    Archive::new(to_gz_decoder_from_bytes(bytes))
-}
+    if config::get().gzip_members {
+        // The head may already hold the content of the first members (if they are small),
+        // and the start of the next
+        let mut files = Vec::with_capacity(stream.members.len());
+        let mut head = head.as_slice();
+        for member in &stream.members {
+            let (content, rest) = head.split_at(member.len);
+            files.push(FileData::from_slice(content));
+            head = rest;
+        }
 
-fn count_archive_files_from_bytes(bytes: &[u8]) -> usize {
-    let mut archive = to_archive_from_bytes(bytes);
-    if let Ok(entries) = archive.entries() {
-        entries.filter_map(|e| e.ok()).count()
-    } else {
-        0
+        // Each other member is hashed as it is decompressed
+        stream.split = true;
+        while stream.gzd.is_some() {
+            let file = FileData::from_reader(head.chain(&mut stream))?;
+            head = &[];
+            // Trailing data after the last member ends the stream without a member
+            if stream.members.len() > files.len() {
+                files.push(file);
+            }
+        }
+        return Ok(get_files_from_members(
+            files,
+            stream.members,
+            fallback,
+            print_info,
+        ));
     }
-}
 
-fn count_archive_entries_from_bytes(bytes: &[u8]) -> usize {
-    let mut archive = to_archive_from_bytes(bytes);
-    if let Ok(entries) = archive.entries() {
-        entries.count()
-    } else {
-        0
+    // Otherwise, the members are one logical stream, named by the first
+    let file = FileData::from_reader(head.chain(&mut stream))?;
+    let name = stream.members.first().and_then(member_name).or(fallback);
+    if let Some(first) = stream.members.first()
+        && print_info
+    {
+        print_member_info(first, name.as_deref());
     }
-}
 
-fn is_tar_gz_bytes(bytes: &[u8]) -> bool {
-    !(count_archive_files_from_bytes(bytes) == 0 || count_archive_entries_from_bytes(bytes) == 0)
+    Ok(vec![ArchiveEntry {
+        path: name,
+        data: EntryData::File(file),
+    }])
 }
 
-// Variant of `get_files_from_gzip_or_tarball` but for reading from a buffer.
-// `context` is used only to recover a file name from plain (non-tarball) gzip content,
-// since raw gzip has no filename field of its own to fall back on.
-//
-// This is used for recursing into nested archives
-pub fn get_files_from_gzip_or_tarball_bytes(bytes: Vec<u8>, context: &Path) -> Vec<ArchiveEntry> {
-    if is_tar_gz_bytes(&bytes) {
-        to_archive_from_bytes(&bytes).get_files_from_tar()
-    } else {
-        let mut gzd = to_gz_decoder_from_bytes(&bytes);
-
-        let name = context.file_stem().map(PathBuf::from);
-        let mut content = Vec::new();
-        gzd.read_to_end(&mut content).unwrap();
-
-        vec![ArchiveEntry {
-            path: name,
-            data: EntryData::File(content),
-        }]
+pub struct Gzip;
+
+impl ArchiveHandler for Gzip {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Gzip
+    }
+
+    fn name(&self) -> &'static str {
+        "gzip"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/gzip"]
+    }
+
+    // If the header has no name for the inner file, it is named from the archive's path
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        let print_info = matches!(origin, Origin::Input(_));
+        get_files_from_gzip(bytes, name, print_info)
+            .map_or_else(|e| origin.read_error("gzip stream", e), Some)
+    }
+
+    fn container_kind(&self, entries: &[ArchiveEntry]) -> Option<&'static str> {
+        package::package_kind(entries)
+    }
+
+    // Container images are tarballs of layers, each a tarball itself, so we list the files
+    // of each layer (or of the image as a whole, once squashed)
+    fn post_process(&self, entries: Vec<ArchiveEntry>, _origin: Origin) -> Vec<ArchiveEntry> {
+        oci::get_image_layers(&entries, config::get().squash).unwrap_or(entries)
     }
 }
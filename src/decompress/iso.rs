@@ -0,0 +1,422 @@
+//! Handle ISO 9660 disc image format
+//!
+//! Read archive files from ISO 9660 disc images (.iso, and often .img), including the Joliet and Rock Ridge extensions for long file names.  Rock Ridge names are preferred, then Joliet, then plain ISO 9660 names.  NOTE: ISO 9660 does not support encryption
+//!
+//! Images which only have a UDF file system (and no ISO 9660 one alongside it) are not yet supported.
+//!
+//! Format references:
+//!   <ecma-international.org/wp-content/uploads/ECMA-119_4th_edition_june_2019.pdf>
+//!   <pismotec.com/cfs/jolspec.html>
+//!   <web.archive.org/web/20170404132301/http://www.ymi.com/ymi/sites/default/files/pdf/Rockridge.pdf>
+
+use super::{ArchiveHandler, Matcher};
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+const SECTOR_SIZE: usize = 2048;
+
+// Volume descriptors start at sector 16, after the system area
+const DESCRIPTORS_START: usize = 16 * SECTOR_SIZE;
+const ISO_IDENTIFIER: &[u8] = b"CD001";
+const UDF_IDENTIFIERS: [&[u8]; 3] = [b"BEA01", b"NSR02", b"NSR03"];
+
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+const DESCRIPTOR_TERMINATOR: u8 = 255;
+
+// Joliet volumes are supplementary volume descriptors with one of these UCS-2 escape
+// sequences
+const JOLIET_ESCAPES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+const ROOT_RECORD_OFFSET: usize = 156;
+
+// Directory record flags
+const FLAG_DIRECTORY: u8 = 1 << 1;
+const FLAG_MULTI_EXTENT: u8 = 1 << 7;
+
+// Rock Ridge name and symlink component flags
+const RR_CONTINUE: u8 = 1 << 0;
+const RR_CURRENT: u8 = 1 << 1;
+const RR_PARENT: u8 = 1 << 2;
+const RR_ROOT: u8 = 1 << 3;
+
+// Returns whether the buffer looks like a disc image, given (at least) its first 17
+// sectors.  We also recognise UDF images, if only to explain that they are unsupported
+//
+// `infer` does not recognise disc images, so this is used as a custom matcher (see
+// [`file`](crate::file))
+fn is_disc_image(buf: &[u8]) -> bool {
+    let Some(identifier) = buf.get(DESCRIPTORS_START + 1..DESCRIPTORS_START + 6) else {
+        return false;
+    };
+    identifier == ISO_IDENTIFIER || UDF_IDENTIFIERS.contains(&identifier)
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Names {
+    Iso,
+    Joliet,
+    RockRidge { skip: usize },
+}
+
+struct Record<'a> {
+    extent: usize,
+    size: usize,
+    flags: u8,
+    name: &'a [u8],
+    system_use: &'a [u8],
+}
+
+fn read_u32_le(buf: &[u8], pos: usize) -> usize {
+    u32::from_le_bytes([buf[pos], buf[pos + 1], buf[pos + 2], buf[pos + 3]]) as usize
+}
+
+fn parse_record(buf: &[u8]) -> Option<Record<'_>> {
+    let len = *buf.first()? as usize;
+    if len < 34 || buf.len() < len {
+        return None;
+    }
+
+    let name_len = buf[32] as usize;
+    let name = buf.get(33..33 + name_len)?;
+
+    // The name is padded to an even length, after which is the system use area
+    let system_use_start = 33 + name_len + (1 - name_len % 2);
+    let system_use = buf.get(system_use_start.min(len)..len)?;
+
+    Some(Record {
+        extent: read_u32_le(buf, 2),
+        size: read_u32_le(buf, 10),
+        flags: buf[25],
+        name,
+        system_use,
+    })
+}
+
+struct Image<'a> {
+    buf: &'a [u8],
+    names: Names,
+}
+
+impl<'a> Image<'a> {
+    fn sector(&self, extent: usize, size: usize) -> Result<&'a [u8], String> {
+        let start = extent * SECTOR_SIZE;
+        self.buf
+            .get(start..start + size)
+            .ok_or_else(|| format!("extent {extent} out of range"))
+    }
+
+    // Walk the System Use Sharing Protocol entries of a record, following continuation
+    // areas, and return the (signature, data) of each
+    fn susp_entries(&self, system_use: &'a [u8]) -> Vec<(&'a [u8], &'a [u8])> {
+        let Names::RockRidge { skip } = self.names else {
+            return Vec::new();
+        };
+
+        let mut entries = Vec::new();
+        let mut area = system_use.get(skip..).unwrap_or_default();
+        let mut visited = HashSet::new();
+
+        loop {
+            let mut continuation = None;
+            let mut pos = 0;
+
+            while pos + 4 <= area.len() {
+                let signature = &area[pos..pos + 2];
+                let len = area[pos + 2] as usize;
+                if len < 4 || pos + len > area.len() {
+                    break;
+                }
+                let data = &area[pos + 4..pos + len];
+
+                match signature {
+                    b"ST" => break,
+                    b"CE" if data.len() >= 24 => {
+                        continuation = Some((
+                            read_u32_le(data, 0),
+                            read_u32_le(data, 8),
+                            read_u32_le(data, 16),
+                        ))
+                    }
+                    _ => entries.push((signature, data)),
+                }
+                pos += len;
+            }
+
+            match continuation {
+                Some((block, offset, len)) if visited.insert((block, offset)) => {
+                    let start = block * SECTOR_SIZE + offset;
+                    area = self.buf.get(start..start + len).unwrap_or_default();
+                }
+                _ => break,
+            }
+        }
+
+        entries
+    }
+
+    fn rock_ridge_name(&self, record: &Record<'a>) -> Option<String> {
+        let mut name = Vec::new();
+        let mut found = false;
+
+        for (signature, data) in self.susp_entries(record.system_use) {
+            if signature == b"NM" && !data.is_empty() {
+                found = true;
+                name.extend_from_slice(&data[1..]);
+                if data[0] & RR_CONTINUE == 0 {
+                    break;
+                }
+            }
+        }
+
+        found.then(|| String::from_utf8_lossy(&name).into_owned())
+    }
+
+    fn rock_ridge_symlink(&self, record: &Record<'a>) -> Option<PathBuf> {
+        let mut target = String::new();
+        let mut found = false;
+        let mut component = String::new();
+
+        for (signature, data) in self.susp_entries(record.system_use) {
+            if signature != b"SL" || data.is_empty() {
+                continue;
+            }
+            found = true;
+
+            // Skip the flags of the SL entry itself, then read each component record
+            let mut pos = 1;
+            while pos + 2 <= data.len() {
+                let flags = data[pos];
+                let len = data[pos + 1] as usize;
+                let content = data.get(pos + 2..pos + 2 + len).unwrap_or_default();
+                pos += 2 + len;
+
+                if flags & RR_ROOT != 0 {
+                    target.push('/');
+                    continue;
+                } else if flags & RR_CURRENT != 0 {
+                    component.push('.');
+                } else if flags & RR_PARENT != 0 {
+                    component.push_str("..");
+                } else {
+                    component.push_str(&String::from_utf8_lossy(content));
+                }
+
+                if flags & RR_CONTINUE == 0 {
+                    if !target.is_empty() && !target.ends_with('/') {
+                        target.push('/');
+                    }
+                    target.push_str(&component);
+                    component.clear();
+                }
+            }
+        }
+
+        found.then(|| PathBuf::from(target))
+    }
+
+    fn record_name(&self, record: &Record<'a>) -> String {
+        match self.names {
+            Names::RockRidge { .. } => {
+                if let Some(name) = self.rock_ridge_name(record) {
+                    return name;
+                }
+            }
+            Names::Joliet => {
+                let units: Vec<u16> = record
+                    .name
+                    .chunks_exact(2)
+                    .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                    .collect();
+                let name = String::from_utf16_lossy(&units);
+                return name.split(';').next().unwrap_or_default().to_string();
+            }
+            Names::Iso => {}
+        }
+
+        // Plain ISO 9660 names are of the form "NAME.EXT;1", where the version and
+        // (if there is no extension) the dot are not part of the file name
+        let name = String::from_utf8_lossy(record.name);
+        let name = name.split(';').next().unwrap_or_default();
+        name.strip_suffix('.').unwrap_or(name).to_string()
+    }
+
+    fn walk_directory(
+        &self,
+        extent: usize,
+        size: usize,
+        prefix: &Path,
+        visited: &mut HashSet<usize>,
+        files: &mut Vec<ArchiveEntry>,
+    ) -> Result<(), String> {
+        // Guard against directory loops in malformed (or malicious) images
+        if !visited.insert(extent) {
+            return Ok(());
+        }
+
+        let directory = self.sector(extent, size)?;
+        let mut pos = 0;
+
+        // Consecutive records of a multi-extent file make up one file
+        let mut pending: Option<(PathBuf, Vec<u8>)> = None;
+
+        while pos < directory.len() {
+            // Records do not cross sector boundaries; a zero length means the rest of
+            // the sector is padding
+            if directory[pos] == 0 {
+                pos = (pos / SECTOR_SIZE + 1) * SECTOR_SIZE;
+                continue;
+            }
+
+            let record = parse_record(&directory[pos..])
+                .ok_or_else(|| format!("malformed directory record in extent {extent}"))?;
+            pos += directory[pos] as usize;
+
+            // Skip the "." and ".." records
+            if record.name == [0] || record.name == [1] {
+                continue;
+            }
+
+            let path = prefix.join(self.record_name(&record));
+
+            if record.flags & FLAG_DIRECTORY != 0 {
+                files.push(ArchiveEntry {
+                    path: Some(path.clone()),
+                    data: EntryData::Directory(Vec::new()),
+                });
+                self.walk_directory(record.extent, record.size, &path, visited, files)?;
+            } else if let Some(target) = self.rock_ridge_symlink(&record) {
+                files.push(ArchiveEntry {
+                    path: Some(path),
+                    data: EntryData::Symlink(target),
+                });
+            } else {
+                let data = self.sector(record.extent, record.size)?;
+                let (path, mut bytes) = match pending.take() {
+                    Some((pending_path, bytes)) if pending_path == path => (path, bytes),
+                    _ => (path, Vec::new()),
+                };
+                bytes.extend_from_slice(data);
+
+                if record.flags & FLAG_MULTI_EXTENT != 0 {
+                    pending = Some((path, bytes));
+                } else {
+                    files.push(ArchiveEntry {
+                        path: Some(path),
+                        data: EntryData::File(FileData::from_bytes(bytes)),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Find the volume descriptor whose directory tree has the best names, returning its
+// root directory record and naming scheme
+fn find_root(buf: &[u8]) -> Result<(Record<'_>, Names), String> {
+    let mut primary = None;
+    let mut joliet = None;
+
+    let mut pos = DESCRIPTORS_START;
+    while let Some(descriptor) = buf.get(pos..pos + SECTOR_SIZE) {
+        if &descriptor[1..6] != ISO_IDENTIFIER {
+            break;
+        }
+
+        match descriptor[0] {
+            DESCRIPTOR_PRIMARY => primary = parse_record(&descriptor[ROOT_RECORD_OFFSET..]),
+            DESCRIPTOR_SUPPLEMENTARY
+                if JOLIET_ESCAPES
+                    .iter()
+                    .any(|e| descriptor[88..120].starts_with(e)) =>
+            {
+                joliet = parse_record(&descriptor[ROOT_RECORD_OFFSET..])
+            }
+            DESCRIPTOR_TERMINATOR => break,
+            _ => {}
+        }
+        pos += SECTOR_SIZE;
+    }
+
+    let Some(primary) = primary else {
+        return Err(if is_disc_image(buf) {
+            String::from("UDF-only images are not yet supported")
+        } else {
+            String::from("missing primary volume descriptor")
+        });
+    };
+
+    // Rock Ridge is signalled by an "SP" entry in the system use area of the root
+    // directory's "." record, which also says how many bytes to skip in every other
+    // record's system use area
+    let image = Image {
+        buf,
+        names: Names::Iso,
+    };
+    let dot = image
+        .sector(primary.extent, SECTOR_SIZE.min(primary.size))
+        .ok()
+        .and_then(parse_record);
+    if let Some(dot) = dot
+        && dot.system_use.len() >= 7
+        && &dot.system_use[0..2] == b"SP"
+        && dot.system_use[4..6] == [0xbe, 0xef]
+    {
+        let skip = dot.system_use[6] as usize;
+        return Ok((primary, Names::RockRidge { skip }));
+    }
+
+    match joliet {
+        Some(joliet) => Ok((joliet, Names::Joliet)),
+        None => Ok((primary, Names::Iso)),
+    }
+}
+
+fn parse_iso(buf: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    let (root, names) = find_root(buf)?;
+    let image = Image { buf, names };
+
+    let mut files = Vec::new();
+    image.walk_directory(
+        root.extent,
+        root.size,
+        Path::new(""),
+        &mut HashSet::new(),
+        &mut files,
+    )?;
+
+    Ok(files)
+}
+
+pub struct Iso;
+
+impl ArchiveHandler for Iso {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Iso
+    }
+
+    fn name(&self) -> &'static str {
+        "iso"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-iso9660-image"]
+    }
+
+    fn matchers(&self) -> &'static [Matcher] {
+        &[Matcher {
+            mime_type: "application/x-iso9660-image",
+            extension: "iso",
+            matches: is_disc_image,
+        }]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        parse_iso(bytes).map_or_else(|e| origin.read_error("disc image", e), Some)
+    }
+}
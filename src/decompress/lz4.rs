@@ -0,0 +1,55 @@
+//! Handle LZ4 archive format
+//!
+//! Read archive files from LZ4 frame and tarball (.tar.lz4) files.  LZ4 only does compression (see [`zstd`](super::zstd)), so plain and tarball content is handled together.  NOTE: LZ4 does not support encryption
+
+use super::{ArchiveHandler, Matcher, tar};
+use crate::archive::{ArchiveEntry, ArchiveType, Origin};
+use lz4_flex::frame::FrameDecoder;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+// Magic bytes of an LZ4 frame
+//   <github.com/lz4/lz4/blob/v1.10.0/doc/lz4_Frame_format.md#general-structure-of-lz4-frame-format>
+const MAGIC: [u8; 4] = [0x04, 0x22, 0x4d, 0x18];
+
+fn is_lz4(buf: &[u8]) -> bool {
+    buf.starts_with(&MAGIC)
+}
+
+// Decompress every frame of an LZ4 stream, reading its content as it is decompressed
+fn get_files_from_lz4<R: Read>(reader: R, name: Option<PathBuf>) -> io::Result<Vec<ArchiveEntry>> {
+    tar::get_files_from_tar_or_stream(FrameDecoder::new(reader), name)
+}
+
+pub struct Lz4;
+
+impl ArchiveHandler for Lz4 {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Lz4
+    }
+
+    fn name(&self) -> &'static str {
+        "lz4"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-lz4"]
+    }
+
+    fn matchers(&self) -> &'static [Matcher] {
+        &[Matcher {
+            mime_type: "application/x-lz4",
+            extension: "lz4",
+            matches: is_lz4,
+        }]
+    }
+
+    // The LZ4 frame format does not store a file name, so we construct the inner file
+    // name from the archive's path
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        get_files_from_lz4(bytes, name).map_or_else(|e| origin.read_error("LZ4 stream", e), Some)
+    }
+}
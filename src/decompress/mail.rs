@@ -0,0 +1,195 @@
+//! Handle email message formats
+//!
+//! Read attachments and other MIME parts from email messages (.eml; RFC 5322 with MIME) and mbox mailboxes.  Each part is decoded from base64 or quoted-printable in memory, and named after its Content-Disposition (or Content-Type) file name.  Archives attached to a message are expanded by recursion (see [`recurse`](crate::recurse)).  NOTE: we do not decrypt S/MIME or PGP-encrypted messages, whose encrypted parts are listed as-is
+//!
+//! Text parts are converted to UTF-8 by the parser, so the hash of a text attachment in another character set will differ from that of the original file.
+//!
+//! Mailboxes are split into messages on "From " lines, and each message's parts are listed in a directory of its own.  Nested messages and mailboxes without attachments are listed as files rather than expanded.
+
+use super::{ArchiveHandler, Matcher};
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use mail_parser::{MessageParser, MimeHeaders, PartType, mailbox::mbox::MessageIterator};
+use std::path::{Path, PathBuf};
+
+// Each message in an mbox mailbox starts with a line like "From sender date"
+//   <www.loc.gov/preservation/digital/formats/fdd/fdd000383.shtml>
+const MBOX_FROM_LINE: &[u8] = b"From ";
+
+// Emails have no magic bytes, so we identify them by their header fields.  We only
+// need a few of the common ones, so long as every line is a header field
+const COMMON_HEADER_FIELDS: [&str; 14] = [
+    "From",
+    "To",
+    "Cc",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "Received",
+    "Return-Path",
+    "MIME-Version",
+    "Delivered-To",
+    "Reply-To",
+    "Sender",
+    "Content-Type",
+    "X-Mailer",
+];
+const MIN_COMMON_HEADER_FIELDS: usize = 2;
+
+// Plain text notes can start with lines like "From:" and "Subject:" too, so we also
+// need one of the fields that only mail software adds
+const MAIL_HEADER_FIELDS: [&str; 2] = ["MIME-Version", "Received"];
+
+// Header blocks can be long (e.g., many "Received" fields), but we stop looking after
+// this many lines
+const MAX_HEADER_LINES: usize = 64;
+
+// Whether the buffer starts with an email message's header block, ended by a blank line
+fn is_email(buf: &[u8]) -> bool {
+    let mut n_common = 0;
+    let mut has_mail_field = false;
+
+    for line in buf.split(|&b| b == b'\n').take(MAX_HEADER_LINES) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        // Blank line separates the headers from the body; folded lines continue the
+        // previous field
+        match line.first() {
+            None => return n_common >= MIN_COMMON_HEADER_FIELDS && has_mail_field,
+            Some(b' ' | b'\t') => continue,
+            Some(_) => {}
+        }
+
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            return false;
+        };
+        let name = &line[..colon];
+        if name.is_empty() || !name.iter().all(u8::is_ascii_graphic) {
+            return false;
+        }
+        let is_field = |field: &&str| name.eq_ignore_ascii_case(field.as_bytes());
+        if COMMON_HEADER_FIELDS.iter().any(is_field) {
+            n_common += 1;
+        }
+        has_mail_field |= MAIL_HEADER_FIELDS.iter().any(is_field);
+    }
+
+    // No blank line (or the end of the data) after the header block
+    false
+}
+
+// Whether the buffer starts with an mbox mailbox's first message
+fn is_mbox(buf: &[u8]) -> bool {
+    buf.strip_prefix(MBOX_FROM_LINE)
+        .and_then(|rest| {
+            rest.iter()
+                .position(|&b| b == b'\n')
+                .map(|i| &rest[i + 1..])
+        })
+        .is_some_and(is_email)
+}
+
+// Default name for a MIME part without a file name of its own
+fn part_name(index: usize, body: &PartType) -> String {
+    let extension = match body {
+        PartType::Text(_) => "txt",
+        PartType::Html(_) => "html",
+        PartType::Message(_) => "eml",
+        _ => "bin",
+    };
+    format!("part-{index}.{extension}")
+}
+
+// Returns an entry for each (non-multipart) MIME part of the message, under the given
+// directory, and the number of those parts that are attachments
+fn get_message_parts(raw: &[u8], dir: &Path) -> Result<(Vec<ArchiveEntry>, usize), String> {
+    let message = MessageParser::default()
+        .parse(raw)
+        .ok_or_else(|| String::from("malformed message"))?;
+
+    let files = message
+        .parts
+        .iter()
+        .enumerate()
+        .filter(|(_, part)| !part.is_multipart())
+        .map(|(i, part)| {
+            // File names cannot be trusted to not contain path separators
+            let name = part
+                .attachment_name()
+                .map(|name| name.replace(['/', '\\'], "_"))
+                .unwrap_or_else(|| part_name(i, &part.body));
+
+            // Attached messages are kept whole, to be expanded by recursion
+            ArchiveEntry {
+                path: Some(dir.join(name)),
+                data: EntryData::File(FileData::from_slice(part.contents())),
+            }
+        })
+        .collect();
+
+    Ok((files, message.attachment_count()))
+}
+
+fn get_files_from_mail_buf(buf: &[u8]) -> Result<(Vec<ArchiveEntry>, usize), String> {
+    if !buf.starts_with(MBOX_FROM_LINE) {
+        return get_message_parts(buf, Path::new(""));
+    }
+
+    let mut files = Vec::new();
+    let mut n_attachments = 0;
+    for (i, message) in MessageIterator::new(buf).enumerate() {
+        let message = message.map_err(|e| e.to_string())?;
+        let dir = PathBuf::from(format!("message-{}", i + 1));
+        files.push(ArchiveEntry {
+            path: Some(dir.clone()),
+            data: EntryData::Directory(Vec::new()),
+        });
+        let (parts, n) = get_message_parts(message.contents(), &dir)?;
+        files.extend(parts);
+        n_attachments += n;
+    }
+
+    Ok((files, n_attachments))
+}
+
+pub struct Mail;
+
+impl ArchiveHandler for Mail {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Mail
+    }
+
+    fn name(&self) -> &'static str {
+        "mail"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["message/rfc822", "application/mbox"]
+    }
+
+    fn matchers(&self) -> &'static [Matcher] {
+        &[
+            Matcher {
+                mime_type: "application/mbox",
+                extension: "mbox",
+                matches: is_mbox,
+            },
+            Matcher {
+                mime_type: "message/rfc822",
+                extension: "eml",
+                matches: is_email,
+            },
+        ]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let (files, n_attachments) = get_files_from_mail_buf(bytes)
+            .map_or_else(|e| origin.read_error("email message", e), Some)?;
+
+        // A nested message without attachments has nothing to expand, so it is listed
+        // as a file of its own
+        if n_attachments == 0 && !matches!(origin, Origin::Input(_)) {
+            return None;
+        }
+        Some(files)
+    }
+}
@@ -1,9 +1,107 @@
 //! A module to handle different decompression formats
 //!
-//! Formats such as zip, 7z, &c.
+//! Formats such as zip, 7z, &c.  Each format has a handler ([`ArchiveHandler`]), registered in [`HANDLERS`], through which we both identify and read archives of that format, whether the input file or nested within another archive
 
+pub mod ar;
+pub mod brotli;
+pub mod cab;
+pub mod compress;
+pub mod cpio;
 pub mod gzip;
+pub mod iso;
+pub mod lz4;
+pub mod mail;
+pub mod msg;
+pub mod oci;
+pub mod ole;
+pub mod package;
+pub mod pdf;
 pub mod rar;
+#[cfg(feature = "unrar")]
+pub mod rar4;
+pub mod rar5;
+pub mod rpm;
 pub mod sevenzip;
+pub mod sfx;
 pub mod tar;
+pub mod xz;
 pub mod zip;
+pub mod zstd;
+
+use crate::archive::{ArchiveEntry, ArchiveType, Origin};
+
+// A magic-byte matcher for a format that `infer` does not know about, and the MIME type
+// (and extension) it identifies
+pub struct Matcher {
+    pub mime_type: &'static str,
+    pub extension: &'static str,
+    pub matches: fn(&[u8]) -> bool,
+}
+
+pub trait ArchiveHandler: Sync {
+    fn archive_type(&self) -> ArchiveType;
+
+    // Short name of the format, as listed among the supported types
+    fn name(&self) -> &'static str;
+
+    // MIME types (as identified by `infer`, or our own matchers) of this format
+    fn mime_types(&self) -> &'static [&'static str];
+
+    // Matchers for this format's magic bytes, if `infer` does not know about it
+    fn matchers(&self) -> &'static [Matcher] {
+        &[]
+    }
+
+    // File extensions, for formats without magic bytes
+    fn extensions(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    // Returns the (flat) entries of the archive, from buffer: either the input file, mapped
+    // into memory, or the content of a nested archive.  Every archive we read is already in
+    // memory, so handlers are given the buffer itself rather than a `Read + Seek` over it;
+    // those which read through a reader wrap it in a `Cursor`, and those which need random
+    // access (most of them) index it directly
+    //
+    // Passwords are asked for through `origin` (see `Origin::password`), and errors (such as
+    // an incorrect password, or a corrupt archive) are reported through it too, so that every
+    // format treats them alike: the input file exits, whereas a nested archive which is not
+    // expanded returns `None`, so that it is still listed (and hashed) as a file
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>>;
+
+    // Many formats are zip archives or tarballs underneath (such as Office documents, or
+    // language packages), so we label which one the (tree-structured) archive is, if any
+    fn container_kind(&self, _entries: &[ArchiveEntry]) -> Option<&'static str> {
+        None
+    }
+
+    // Post-process the (tree-structured) entries of the archive once read, such as to verify
+    // a package against its own record of its contents.  This is done wherever the archive
+    // came from, so that nested archives are treated just as the input file is
+    fn post_process(&self, entries: Vec<ArchiveEntry>, _origin: Origin) -> Vec<ArchiveEntry> {
+        entries
+    }
+}
+
+// Every format we read.  Custom matchers are tried in this order
+pub static HANDLERS: &[&dyn ArchiveHandler] = &[
+    &zip::Zip,
+    &sevenzip::SevenZip,
+    &gzip::Gzip,
+    &tar::Tar,
+    &rar::Rar,
+    &zstd::Zstd,
+    &lz4::Lz4,
+    &brotli::Brotli,
+    &compress::Compress,
+    &xz::Xz,
+    &cpio::Cpio,
+    &ar::Ar,
+    &rpm::Rpm,
+    &iso::Iso,
+    &cab::Cab,
+    &ole::Ole,
+    &sfx::Sfx,
+    &mail::Mail,
+    &pdf::Pdf,
+];
@@ -0,0 +1,314 @@
+//! Handle Outlook message format
+//!
+//! Read the body and attachments of Outlook messages (.msg).  These are OLE compound files (see [`ole`](super::ole)), with each property of the message in a stream of its own, and each attachment in a storage of its own.  Attached messages are themselves stored as storages within the attachment's, and are listed as directories.  Archives attached to a message are expanded by recursion (see [`recurse`](crate::recurse)).  NOTE: we do not decrypt S/MIME-encrypted messages, whose encrypted attachment is listed as-is
+//!
+//! Text bodies are converted to UTF-8, and the compressed RTF body is decompressed.
+//!
+//! Format reference:
+//!   <learn.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-oxmsg>
+
+use crate::archive::{ArchiveEntry, EntryData, FileData};
+use cfb::CompoundFile;
+use std::{
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+// Every message has a named property mapping storage at its root
+const NAMED_PROPERTY_MAPPING_STORAGE: &str = "__nameid_version1.0";
+
+// Each property is stored in a stream named for its tag (ID and type)
+const PROPERTY_STREAM_PREFIX: &str = "__substg1.0_";
+const ATTACHMENT_STORAGE_PREFIX: &str = "__attach_version1.0_#";
+
+// Property types
+const PT_STRING8: u16 = 0x001e;
+const PT_UNICODE: u16 = 0x001f;
+const PT_BINARY: u16 = 0x0102;
+const PT_OBJECT: u16 = 0x000d;
+
+// Property IDs
+//   <learn.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-oxprops>
+const PID_TAG_SUBJECT: u16 = 0x0037;
+const PID_TAG_BODY: u16 = 0x1000;
+const PID_TAG_RTF_COMPRESSED: u16 = 0x1009;
+const PID_TAG_BODY_HTML: u16 = 0x1013;
+const PID_TAG_DISPLAY_NAME: u16 = 0x3001;
+const PID_TAG_ATTACH_DATA: u16 = 0x3701;
+const PID_TAG_ATTACH_FILENAME: u16 = 0x3704;
+const PID_TAG_ATTACH_LONG_FILENAME: u16 = 0x3707;
+
+// Compressed RTF starts with a header naming its compression, and has a dictionary
+// primed with common RTF
+//   <learn.microsoft.com/en-us/openspecs/exchange_server_protocols/ms-oxrtfcp>
+const RTF_HEADER_LEN: usize = 16;
+const RTF_COMPRESSED: &[u8] = b"LZFu";
+const RTF_UNCOMPRESSED: &[u8] = b"MELA";
+const RTF_DICTIONARY_LEN: usize = 4096;
+const RTF_DICTIONARY_PRIMER: &[u8] = b"{\\rtf1\\ansi\\mac\\deff0\\deftab720{\\fonttbl;}{\\f0\\fnil \\froman \\fswiss \\fmodern \\fscript \\fdecor MS Sans SerifSymbolArialTimes New RomanCourier{\\colortbl\\red0\\green0\\blue0\r\n\\par \\pard\\plain\\f0\\fs20\\b\\i\\u\\tab\\tx";
+
+// Whether the compound file is an Outlook message, rather than any other compound file
+pub fn is_msg<F>(compound_file: &CompoundFile<F>) -> bool {
+    compound_file.is_storage(Path::new("/").join(NAMED_PROPERTY_MAPPING_STORAGE))
+}
+
+fn property_path(storage: &Path, id: u16, property_type: u16) -> PathBuf {
+    storage.join(format!(
+        "{PROPERTY_STREAM_PREFIX}{id:04X}{property_type:04X}"
+    ))
+}
+
+fn read_stream<F: Read + Seek>(
+    compound_file: &mut CompoundFile<F>,
+    path: &Path,
+) -> io::Result<Option<Vec<u8>>> {
+    if !compound_file.is_stream(path) {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    compound_file.open_stream(path)?.read_to_end(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+// String properties are either UTF-16 or 8-bit (in the message's code page, which we
+// can only guess at)
+fn read_string<F: Read + Seek>(
+    compound_file: &mut CompoundFile<F>,
+    storage: &Path,
+    id: u16,
+) -> io::Result<Option<String>> {
+    if let Some(bytes) = read_stream(compound_file, &property_path(storage, id, PT_UNICODE))? {
+        let units = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect::<Vec<_>>();
+        let string = String::from_utf16_lossy(&units);
+        return Ok(Some(string.trim_end_matches('\0').to_string()));
+    }
+    let string =
+        read_stream(compound_file, &property_path(storage, id, PT_STRING8))?.map(|bytes| {
+            String::from_utf8_lossy(&bytes)
+                .trim_end_matches('\0')
+                .to_string()
+        });
+    Ok(string)
+}
+
+// Decompress an RTF body, which is usually compressed with a simple LZ77 variant
+fn decompress_rtf(buf: &[u8]) -> Result<Vec<u8>, String> {
+    let header = buf
+        .get(..RTF_HEADER_LEN)
+        .ok_or_else(|| String::from("truncated compressed RTF header"))?;
+    let raw_size = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    let data = &buf[RTF_HEADER_LEN..];
+
+    match &header[8..12] {
+        RTF_UNCOMPRESSED => return Ok(data.iter().copied().take(raw_size).collect()),
+        RTF_COMPRESSED => {}
+        _ => return Err(String::from("unknown RTF compression type")),
+    }
+
+    let mut dictionary = [0; RTF_DICTIONARY_LEN];
+    dictionary[..RTF_DICTIONARY_PRIMER.len()].copy_from_slice(RTF_DICTIONARY_PRIMER);
+    let mut write_pos = RTF_DICTIONARY_PRIMER.len();
+
+    // The raw size is only trusted as far as the data could decompress to (each reference
+    // being at most 17 bytes, from two), and we stop once it is reached
+    let mut out = Vec::with_capacity(raw_size.min(data.len().saturating_mul(8)));
+    let mut input = data.iter().copied();
+
+    // Each control byte says whether each of the next eight tokens is a literal (0) or
+    // a reference into the dictionary (1)
+    'outer: while let Some(control) = input.next() {
+        for bit in 0..8 {
+            if out.len() == raw_size {
+                break 'outer;
+            }
+            if control & (1 << bit) == 0 {
+                let Some(byte) = input.next() else {
+                    break 'outer;
+                };
+                out.push(byte);
+                dictionary[write_pos] = byte;
+                write_pos = (write_pos + 1) % RTF_DICTIONARY_LEN;
+                continue;
+            }
+
+            let (Some(hi), Some(lo)) = (input.next(), input.next()) else {
+                break 'outer;
+            };
+            let reference = u16::from_be_bytes([hi, lo]) as usize;
+            let offset = reference >> 4;
+            let len = (reference & 0xf) + 2;
+
+            // A reference to the current write position marks the end of the stream
+            if offset == write_pos {
+                break 'outer;
+            }
+            for i in 0..len.min(raw_size - out.len()) {
+                let byte = dictionary[(offset + i) % RTF_DICTIONARY_LEN];
+                out.push(byte);
+                dictionary[write_pos] = byte;
+                write_pos = (write_pos + 1) % RTF_DICTIONARY_LEN;
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+// File names cannot be trusted to not contain path separators
+fn sanitise_name(name: &str) -> String {
+    name.replace(['/', '\\'], "_")
+}
+
+// Add entries for the body and attachments of the message in the given storage, under
+// the given directory
+fn add_message_entries<F: Read + Seek>(
+    compound_file: &mut CompoundFile<F>,
+    storage: &Path,
+    dir: &Path,
+    files: &mut Vec<ArchiveEntry>,
+) -> io::Result<()> {
+    if let Some(body) = read_string(compound_file, storage, PID_TAG_BODY)? {
+        files.push(ArchiveEntry {
+            path: Some(dir.join("body.txt")),
+            data: EntryData::File(FileData::from_bytes(body.into_bytes())),
+        });
+    }
+
+    let html = match read_stream(
+        compound_file,
+        &property_path(storage, PID_TAG_BODY_HTML, PT_BINARY),
+    )? {
+        Some(html) => Some(html),
+        None => read_string(compound_file, storage, PID_TAG_BODY_HTML)?.map(String::into_bytes),
+    };
+    if let Some(html) = html {
+        files.push(ArchiveEntry {
+            path: Some(dir.join("body.html")),
+            data: EntryData::File(FileData::from_bytes(html)),
+        });
+    }
+
+    if let Some(rtf) = read_stream(
+        compound_file,
+        &property_path(storage, PID_TAG_RTF_COMPRESSED, PT_BINARY),
+    )? {
+        match decompress_rtf(&rtf) {
+            Ok(rtf) => files.push(ArchiveEntry {
+                path: Some(dir.join("body.rtf")),
+                data: EntryData::File(FileData::from_bytes(rtf)),
+            }),
+            Err(e) => eprintln!(
+                "[WARN] Skipping RTF body of {} ({e})",
+                dir.join("body.rtf").display()
+            ),
+        }
+    }
+
+    let mut attachments = compound_file
+        .read_storage(storage)?
+        .filter(|entry| entry.is_storage() && entry.name().starts_with(ATTACHMENT_STORAGE_PREFIX))
+        .map(|entry| entry.path().to_path_buf())
+        .collect::<Vec<_>>();
+    attachments.sort();
+
+    for (i, attachment) in attachments.iter().enumerate() {
+        let mut name = None;
+        for id in [
+            PID_TAG_ATTACH_LONG_FILENAME,
+            PID_TAG_ATTACH_FILENAME,
+            PID_TAG_DISPLAY_NAME,
+        ] {
+            name = read_string(compound_file, attachment, id)?.filter(|name| !name.is_empty());
+            if name.is_some() {
+                break;
+            }
+        }
+
+        // Attached messages (and other OLE objects) are storages, rather than streams
+        let object = property_path(attachment, PID_TAG_ATTACH_DATA, PT_OBJECT);
+        if compound_file.is_storage(&object) {
+            let is_message = compound_file
+                .read_storage(&object)?
+                .any(|entry| entry.name().starts_with(PROPERTY_STREAM_PREFIX));
+            let name = match name {
+                Some(name) => sanitise_name(&name),
+                None if is_message => {
+                    let subject = read_string(compound_file, &object, PID_TAG_SUBJECT)?
+                        .filter(|subject| !subject.is_empty());
+                    match subject {
+                        Some(subject) => format!("{}.msg", sanitise_name(&subject)),
+                        None => format!("attachment-{}.msg", i + 1),
+                    }
+                }
+                None => format!("attachment-{}", i + 1),
+            };
+
+            let object_dir = dir.join(name);
+            files.push(ArchiveEntry {
+                path: Some(object_dir.clone()),
+                data: EntryData::Directory(Vec::new()),
+            });
+            if is_message {
+                add_message_entries(compound_file, &object, &object_dir, files)?;
+            } else {
+                add_storage_entries(compound_file, &object, &object_dir, files)?;
+            }
+            continue;
+        }
+
+        let name = name
+            .map(|name| sanitise_name(&name))
+            .unwrap_or_else(|| format!("attachment-{}", i + 1));
+        let path = property_path(attachment, PID_TAG_ATTACH_DATA, PT_BINARY);
+        match read_stream(compound_file, &path)? {
+            Some(data) => files.push(ArchiveEntry {
+                path: Some(dir.join(name)),
+                data: EntryData::File(FileData::from_bytes(data)),
+            }),
+            // Attachments by reference (such as links to cloud storage) have no data
+            None => eprintln!(
+                "[WARN] Skipping attachment without data: {}",
+                dir.join(name).display()
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+// Add entries for each stream of an attached OLE object (that is not a message)
+fn add_storage_entries<F: Read + Seek>(
+    compound_file: &mut CompoundFile<F>,
+    storage: &Path,
+    dir: &Path,
+    files: &mut Vec<ArchiveEntry>,
+) -> io::Result<()> {
+    let streams = compound_file
+        .walk_storage(storage)?
+        .filter(|entry| entry.is_stream())
+        .map(|entry| entry.path().to_path_buf())
+        .collect::<Vec<_>>();
+
+    for stream in streams {
+        let relative = stream.strip_prefix(storage).unwrap_or(&stream);
+        let data = read_stream(compound_file, &stream)?.unwrap_or_default();
+        files.push(ArchiveEntry {
+            path: Some(dir.join(relative)),
+            data: EntryData::File(FileData::from_bytes(data)),
+        });
+    }
+
+    Ok(())
+}
+
+// Returns a vector of archive entries pertaining to the message's body and attachments
+pub fn read_message<F: Read + Seek>(
+    compound_file: &mut CompoundFile<F>,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut files = Vec::new();
+    add_message_entries(compound_file, Path::new("/"), Path::new(""), &mut files)?;
+    Ok(files)
+}
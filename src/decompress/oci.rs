@@ -0,0 +1,324 @@
+//! Handle container image layouts
+//!
+//! Read the layers of container images saved as tarballs, in either the Docker (`docker save`) or OCI image layout.  Images are tarballs of tarballs: each layer is a (usually gzip- or Zstandard-compressed) tarball of the files it adds or changes, listed in order by the image's manifest.  We expand each layer in memory, and list the files of each in a directory of its own.  NOTE: image layouts do not support encryption
+//!
+//! Alternatively, the layers may be squashed into the image's final filesystem, by applying each in order.  Layers remove files from those below them with whiteout files (`.wh.<name>`), or hide the contents of a directory entirely with an opaque whiteout (`.wh..wh..opq`).
+//!
+//! Format references:
+//!   <github.com/moby/docker-image-spec/blob/v1.3.1/spec.md>
+//!   <github.com/opencontainers/image-spec/blob/v1.1.0/image-layout.md>
+//!   <github.com/opencontainers/image-spec/blob/v1.1.0/layer.md>
+
+use super::tar;
+use crate::{
+    archive::{ArchiveEntry, EntryData, FileData},
+    tree::{self, find_entry},
+};
+use flate2::read::MultiGzDecoder;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    io::Read,
+    ops::Bound,
+    path::{Component, Path, PathBuf},
+};
+
+// Docker image layouts have a manifest listing each image's configuration and layers,
+// while OCI image layouts have an index of manifests, each of which is a blob
+const DOCKER_MANIFEST: &str = "manifest.json";
+const OCI_LAYOUT: &str = "oci-layout";
+const OCI_INDEX: &str = "index.json";
+const OCI_BLOBS_DIR: &str = "blobs";
+const OCI_REF_NAME_ANNOTATION: &str = "org.opencontainers.image.ref.name";
+
+// Indices may refer to other indices (such as for multi-platform images), but we stop
+// following them after this many
+const MAX_INDEX_DEPTH: usize = 8;
+
+// Whiteout files mark the removal of a file from the layers below
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+
+// Magic bytes of compressed layers
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// Layers are named by their digest, abbreviated as Docker does
+const SHORT_DIGEST_LEN: usize = 12;
+
+struct Image {
+    name: Option<String>,
+    config: Option<String>,
+    layers: Vec<String>,
+}
+
+fn read_json(entries: &[ArchiveEntry], path: &str) -> Option<Value> {
+    match find_entry(entries, path).map(|entry| &entry.data) {
+        Some(EntryData::File(FileData {
+            bytes: Some(bytes), ..
+        })) => serde_json::from_slice(bytes).ok(),
+        _ => None,
+    }
+}
+
+fn strings(value: &Value) -> impl Iterator<Item = &str> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+// Docker's manifest lists each image's configuration and layers by their path
+fn docker_images(manifest: &Value) -> Option<Vec<Image>> {
+    manifest
+        .as_array()?
+        .iter()
+        .map(|image| {
+            Some(Image {
+                name: strings(&image["RepoTags"]).next().map(String::from),
+                config: image["Config"].as_str().map(String::from),
+                layers: strings(image.get("Layers")?).map(String::from).collect(),
+            })
+        })
+        .collect()
+}
+
+// OCI blobs are stored by their digest ("<algorithm>:<hex>")
+fn blob_path(descriptor: &Value) -> Option<String> {
+    let (algorithm, hex) = descriptor["digest"].as_str()?.split_once(':')?;
+    Some(format!("{OCI_BLOBS_DIR}/{algorithm}/{hex}"))
+}
+
+fn oci_images(entries: &[ArchiveEntry], index: &Value, depth: usize, images: &mut Vec<Image>) {
+    if depth > MAX_INDEX_DEPTH {
+        eprintln!("[WARN] Skipping image index (too deeply nested)");
+        return;
+    }
+
+    for descriptor in index["manifests"].as_array().into_iter().flatten() {
+        let Some(manifest) = blob_path(descriptor).and_then(|path| read_json(entries, &path))
+        else {
+            continue;
+        };
+
+        // An index may list other indices, rather than image manifests
+        if manifest.get("manifests").is_some() {
+            oci_images(entries, &manifest, depth + 1, images);
+            continue;
+        }
+
+        images.push(Image {
+            name: descriptor["annotations"][OCI_REF_NAME_ANNOTATION]
+                .as_str()
+                .map(String::from),
+            config: blob_path(&manifest["config"]),
+            layers: manifest["layers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(blob_path)
+                .collect(),
+        });
+    }
+}
+
+// Images saved by Docker may have both layouts, in which case we prefer Docker's
+// manifest, as it names each image by its tags
+fn find_images(entries: &[ArchiveEntry]) -> Option<Vec<Image>> {
+    if let Some(images) = read_json(entries, DOCKER_MANIFEST).and_then(|m| docker_images(&m)) {
+        return Some(images);
+    }
+
+    find_entry(entries, OCI_LAYOUT)?;
+    let mut images = Vec::new();
+    oci_images(entries, &read_json(entries, OCI_INDEX)?, 0, &mut images);
+    Some(images)
+}
+
+// Returns the (flat) entries of a layer, which may be compressed.  The layer is read as it
+// is decompressed
+fn read_layer(bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    let mut reader: Box<dyn Read + '_> = if bytes.starts_with(GZIP_MAGIC) {
+        Box::new(MultiGzDecoder::new(bytes))
+    } else if bytes.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::stream::read::Decoder::new(bytes).map_err(|e| e.to_string())?)
+    } else {
+        Box::new(bytes)
+    };
+
+    let mut head = Vec::with_capacity(512);
+    reader
+        .by_ref()
+        .take(512)
+        .read_to_end(&mut head)
+        .map_err(|e| e.to_string())?;
+    if !tar::is_tar_header(&head) {
+        return Err(String::from("not a tarball"));
+    }
+    tar::get_files_from_tar_stream(head.chain(reader)).map_err(|e| e.to_string())
+}
+
+// Name a layer by its index and abbreviated digest (taken from its blob's name, or in
+// Docker's older layout, from its directory's name)
+fn layer_name(index: usize, path: &str) -> String {
+    let path = Path::new(path);
+    let digest = match path.file_name().and_then(|name| name.to_str()) {
+        Some("layer.tar") => path.parent().and_then(Path::file_name),
+        _ => path.file_name(),
+    };
+    match digest.and_then(|digest| digest.to_str()) {
+        Some(digest) => format!(
+            "layer-{index}-{}",
+            &digest[..digest.len().min(SHORT_DIGEST_LEN)]
+        ),
+        None => format!("layer-{index}"),
+    }
+}
+
+// Layer paths are relative to the root of the image's filesystem, often with a leading
+// "./", so only the normal components are kept
+fn normalise(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+// Remove a path and everything beneath it from the filesystem.  Descendants sort
+// immediately after their directory, as paths are ordered by component
+fn remove_tree(filesystem: &mut BTreeMap<PathBuf, ArchiveEntry>, path: &Path, include_self: bool) {
+    let descendants = filesystem
+        .range::<Path, _>((Bound::Excluded(path), Bound::Unbounded))
+        .map(|(key, _)| key)
+        .take_while(|key| key.starts_with(path))
+        .cloned()
+        .collect::<Vec<_>>();
+    for key in descendants {
+        filesystem.remove(&key);
+    }
+    if include_self {
+        filesystem.remove(path);
+    }
+}
+
+// Apply each layer in order to find the image's final filesystem
+fn squash(layers: Vec<Vec<ArchiveEntry>>) -> Vec<ArchiveEntry> {
+    let mut filesystem = BTreeMap::new();
+
+    for layer in layers {
+        // Whiteouts only apply to the layers below, so are applied before the layer's
+        // own files are added
+        let (whiteouts, files): (Vec<_>, Vec<_>) = layer
+            .into_iter()
+            .filter_map(|entry| Some((normalise(entry.path.as_deref()?), entry)))
+            .partition(|(path, _)| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(WHITEOUT_PREFIX))
+            });
+
+        for (path, _) in whiteouts {
+            let parent = path.parent().unwrap_or(Path::new(""));
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if name == OPAQUE_WHITEOUT {
+                remove_tree(&mut filesystem, parent, false);
+            } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+                remove_tree(&mut filesystem, &parent.join(hidden), true);
+            }
+        }
+
+        for (path, entry) in files {
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            let is_dir = matches!(entry.data, EntryData::Directory(_));
+            let entry = ArchiveEntry {
+                path: Some(path.clone()),
+                data: entry.data,
+            };
+
+            // A file replacing a directory replaces its contents too
+            if let Some(old) = filesystem.insert(path.clone(), entry)
+                && matches!(old.data, EntryData::Directory(_))
+                && !is_dir
+            {
+                remove_tree(&mut filesystem, &path, false);
+            }
+        }
+    }
+
+    tree::build_tree(filesystem.into_values().collect())
+}
+
+fn get_image_entries(entries: &[ArchiveEntry], image: &Image, squashed: bool) -> Vec<ArchiveEntry> {
+    let mut layers = Vec::with_capacity(image.layers.len());
+    for (i, path) in image.layers.iter().enumerate() {
+        let name = layer_name(i + 1, path);
+        let layer = match find_entry(entries, path).map(|entry| &entry.data) {
+            Some(EntryData::File(FileData {
+                bytes: Some(bytes), ..
+            })) => read_layer(bytes),
+            Some(EntryData::File(_)) => Err(String::from(
+                "too large to keep in memory; see --buffer-limit",
+            )),
+            // Non-distributable layers (such as Windows base layers) may be omitted
+            _ => Err(String::from("missing from image")),
+        };
+        match layer {
+            Ok(files) => layers.push((name, files)),
+            Err(e) => eprintln!("[WARN] Skipping layer ({e}): {path}"),
+        }
+    }
+
+    if squashed {
+        return squash(layers.into_iter().map(|(_, files)| files).collect());
+    }
+
+    // The image's configuration is hashed to give its ID, so we list it too
+    let config = image
+        .config
+        .as_deref()
+        .and_then(|path| find_entry(entries, path))
+        .and_then(|entry| match &entry.data {
+            EntryData::File(file) => Some(ArchiveEntry {
+                path: Some(PathBuf::from("config.json")),
+                data: EntryData::File(file.clone()),
+            }),
+            _ => None,
+        });
+
+    config
+        .into_iter()
+        .chain(layers.into_iter().map(|(name, files)| ArchiveEntry {
+            path: Some(PathBuf::from(name)),
+            data: EntryData::NestedArchive(tree::build_tree(files)),
+        }))
+        .collect()
+}
+
+// Returns the entries of each image, by layer (or squashed), if the (tree-structured)
+// archive is a container image layout
+//
+// Layouts with more than one image list each in a directory named for its tag
+pub fn get_image_layers(entries: &[ArchiveEntry], squashed: bool) -> Option<Vec<ArchiveEntry>> {
+    let images = find_images(entries)?;
+    if let [image] = images.as_slice() {
+        return Some(get_image_entries(entries, image, squashed));
+    }
+
+    let files = images
+        .iter()
+        .enumerate()
+        .map(|(i, image)| {
+            let name = match &image.name {
+                Some(name) => name.replace(['/', '\\'], "_"),
+                None => format!("image-{}", i + 1),
+            };
+            ArchiveEntry {
+                path: Some(PathBuf::from(name)),
+                data: EntryData::Directory(get_image_entries(entries, image, squashed)),
+            }
+        })
+        .collect();
+    Some(files)
+}
@@ -0,0 +1,142 @@
+//! Handle OLE compound file format
+//!
+//! Read streams from OLE compound files (also known as Compound File Binary, or CFB), as used by Windows Installer packages (.msi) and legacy Microsoft Office documents (.doc, .xls, .ppt).  Storages are listed as directories, and streams as files (except for Outlook messages, whose body and attachments are listed instead; see [`msg`](super::msg)); any archives embedded in streams (such as the cabinets within an installer package) are expanded by recursion (see [`recurse`](crate::recurse)).  NOTE: we do not decrypt encrypted Office documents, whose streams are listed as-is
+//!
+//! Format reference:
+//!   <learn.microsoft.com/en-us/openspecs/windows_protocols/ms-cfb>
+
+use super::{ArchiveHandler, msg};
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use cfb::CompoundFile;
+use std::{
+    io::{Cursor, Read, Seek},
+    path::{Component, PathBuf},
+};
+
+// Class IDs of the root storage of Windows Installer packages, patches, and transforms
+//   <learn.microsoft.com/en-us/windows/win32/msi/installer-database>
+const MSI_CLSIDS: [&str; 3] = [
+    "000c1084-0000-0000-c000-000000000046",
+    "000c1086-0000-0000-c000-000000000046",
+    "000c1082-0000-0000-c000-000000000046",
+];
+
+// Windows Installer packs stream names into code points from 0x3800, storing two
+// characters from this alphabet in each; code points from 0x4800 store one character,
+// and 0x4840 prefixes the names of the database's tables
+//   <github.com/wine-mirror/wine/blob/wine-9.0/dlls/msi/table.c>
+const MSI_NAME_ALPHABET: &[u8; 64] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz._";
+const MSI_NAME_PAIR_START: u32 = 0x3800;
+const MSI_NAME_SINGLE_START: u32 = 0x4800;
+const MSI_TABLE_PREFIX: u32 = 0x4840;
+
+fn decode_msi_name(name: &str) -> String {
+    let mut decoded = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c as u32 {
+            n @ MSI_NAME_PAIR_START..MSI_NAME_SINGLE_START => {
+                let n = n - MSI_NAME_PAIR_START;
+                decoded.push(MSI_NAME_ALPHABET[(n & 0x3f) as usize] as char);
+                decoded.push(MSI_NAME_ALPHABET[((n >> 6) & 0x3f) as usize] as char);
+            }
+            n @ MSI_NAME_SINGLE_START..MSI_TABLE_PREFIX => {
+                decoded.push(MSI_NAME_ALPHABET[(n - MSI_NAME_SINGLE_START) as usize] as char);
+            }
+            MSI_TABLE_PREFIX => decoded.push('!'),
+            _ => decoded.push(c),
+        }
+    }
+    decoded
+}
+
+// Some stream names start with a control character (such as "\x05SummaryInformation"),
+// which we show in brackets instead, as 7-Zip does
+fn printable_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_control() {
+                format!("[{}]", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+fn read_compound_file<F: Read + Seek>(inner: F) -> std::io::Result<Vec<ArchiveEntry>> {
+    let mut compound_file = CompoundFile::open(inner)?;
+
+    // Outlook messages are better listed by their body and attachments
+    if msg::is_msg(&compound_file) {
+        return msg::read_message(&mut compound_file);
+    }
+
+    let is_msi = MSI_CLSIDS.contains(&compound_file.root_entry().clsid().to_string().as_str());
+    let decode_name = |name: &str| {
+        if is_msi {
+            printable_name(&decode_msi_name(name))
+        } else {
+            printable_name(name)
+        }
+    };
+
+    // Streams can only be opened once we are done walking the directory
+    let entries = compound_file
+        .walk()
+        .filter(|entry| !entry.is_root())
+        .map(|entry| (entry.path().to_path_buf(), entry.is_storage()))
+        .collect::<Vec<_>>();
+
+    let mut files = Vec::with_capacity(entries.len());
+    for (path, is_storage) in entries {
+        let display_path = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(decode_name(&name.to_string_lossy())),
+                _ => None,
+            })
+            .collect::<PathBuf>();
+
+        let data = if is_storage {
+            EntryData::Directory(Vec::new())
+        } else {
+            let mut bytes = Vec::new();
+            compound_file.open_stream(&path)?.read_to_end(&mut bytes)?;
+            EntryData::File(FileData::from_bytes(bytes))
+        };
+
+        files.push(ArchiveEntry {
+            path: Some(display_path),
+            data,
+        });
+    }
+
+    Ok(files)
+}
+
+pub struct Ole;
+
+impl ArchiveHandler for Ole {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Ole
+    }
+
+    fn name(&self) -> &'static str {
+        "ole"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/x-ole-storage",
+            "application/msword",
+            "application/vnd.ms-excel",
+            "application/vnd.ms-powerpoint",
+        ]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        read_compound_file(Cursor::new(bytes))
+            .map_or_else(|e| origin.read_error("OLE compound file", e), Some)
+    }
+}
@@ -0,0 +1,283 @@
+//! Handle language package formats
+//!
+//! Identify packages from language ecosystems, which are ordinary zip archives or tarballs with a particular layout: npm packages (`.tgz`), Python wheels (`.whl`) and source distributions (`.tar.gz`), Ruby gems (`.gem`), and Rust crates (`.crate`).  Where the package records the digests of its own contents, we compare them against the digests of the files we extracted, warning about any mismatch as it may be evidence of tampering:
+//!   - Wheels list the digest of every file in their `RECORD`;
+//!   - Gems list the digests of their `metadata.gz` and `data.tar.gz` in `checksums.yaml.gz`.
+//!
+//! The contents of a gem's `data.tar.gz` are expanded by recursion (see [`recurse`](crate::recurse)).
+//!
+//! Format references:
+//!   <packaging.python.org/en/latest/specifications/binary-distribution-format>
+//!   <packaging.python.org/en/latest/specifications/recording-installed-packages>
+//!   <guides.rubygems.org/specification-reference>
+//!   <docs.npmjs.com/cli/v10/commands/npm-pack>
+//!   <doc.rust-lang.org/cargo/commands/cargo-package.html>
+
+use crate::{
+    archive::{ArchiveEntry, EntryData},
+    hash,
+    tree::find_entry,
+};
+use digest::Digest;
+use flate2::read::GzDecoder;
+use sha1::Sha1;
+use sha2::{Sha256, Sha384, Sha512};
+use std::{collections::HashMap, io::Read, path::Path};
+
+// Wheels keep their metadata in a "<name>-<version>.dist-info" directory
+const WHEEL_METADATA_DIR_SUFFIX: &str = ".dist-info";
+const WHEEL_RECORD: &str = "RECORD";
+
+// Gems are (uncompressed) tarballs of these, and usually a signature for each
+const GEM_METADATA: &str = "metadata.gz";
+const GEM_DATA: &str = "data.tar.gz";
+const GEM_CHECKSUMS: &str = "checksums.yaml.gz";
+
+// Wheel digests are encoded in URL-safe base64, without padding
+const BASE64_URL_SAFE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn base64_url_safe(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            encoded.push(BASE64_URL_SAFE_ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    encoded
+}
+
+// Packages whose contents are all within a single top-level directory (such as
+// "<name>-<version>/"), return that directory's name
+fn single_top_level_dir(entries: &[ArchiveEntry]) -> Option<String> {
+    match entries {
+        [entry] if matches!(entry.data, EntryData::Directory(_)) => Some(entry.name()),
+        _ => None,
+    }
+}
+
+// Tarball packages are identified by the files each is required to contain (wheels are
+// zip archives, so are identified with them; see [`zip`](super::zip))
+pub fn package_kind(entries: &[ArchiveEntry]) -> Option<&'static str> {
+    let has = |path: &str| find_entry(entries, path).is_some();
+
+    if has("package/package.json") {
+        return Some("npm package");
+    }
+    if has(GEM_METADATA) && has(GEM_DATA) {
+        return Some("Ruby gem");
+    }
+
+    let dir = single_top_level_dir(entries)?;
+    if has(&format!("{dir}/Cargo.toml"))
+        && (has(&format!("{dir}/Cargo.toml.orig")) || has(&format!("{dir}/.cargo_vcs_info.json")))
+    {
+        return Some("Rust crate");
+    }
+    if has(&format!("{dir}/PKG-INFO")) {
+        return Some("Python source distribution (sdist)");
+    }
+
+    None
+}
+
+// Returns each file in the (tree-structured) archive with its slash-separated path, and
+// its content, if kept in memory
+type Files<'a> = Vec<(String, Option<&'a [u8]>)>;
+
+fn walk_files<'a>(entries: &'a [ArchiveEntry], prefix: &str, files: &mut Files<'a>) {
+    for entry in entries {
+        let path = format!("{prefix}{}", entry.name());
+        match &entry.data {
+            EntryData::File(file) => files.push((path, file.bytes.as_deref())),
+            EntryData::Directory(children) => walk_files(children, &format!("{path}/"), files),
+            _ => {}
+        }
+    }
+}
+
+// Files larger than the buffer limit are hashed as they are read, but not kept, so we cannot
+// compute their digest again with the package's algorithm
+fn warn_too_large(path: &Path) {
+    eprintln!(
+        "[WARN] File too large to keep in memory (see --buffer-limit); skipping verification: {}",
+        path.display()
+    );
+}
+
+fn wheel_digest(algo: &str, data: &[u8]) -> Option<String> {
+    let digest = match algo {
+        "sha256" => Sha256::digest(data).to_vec(),
+        "sha384" => Sha384::digest(data).to_vec(),
+        "sha512" => Sha512::digest(data).to_vec(),
+        _ => return None,
+    };
+    Some(base64_url_safe(&digest))
+}
+
+// Split a line of a RECORD (which is CSV) into its fields.  Paths containing commas are
+// quoted
+fn record_fields(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(String::new()),
+            _ => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+// Compare the digest of every file in a wheel against the digest recorded for it in the
+// wheel's RECORD, which lists every file (including those outside the .dist-info
+// directory)
+fn verify_wheel_record(entries: &[ArchiveEntry], record: &[u8], context: &Path) {
+    let mut files = Vec::new();
+    walk_files(entries, "", &mut files);
+    let mut unlisted = files.iter().cloned().collect::<HashMap<_, _>>();
+
+    for line in String::from_utf8_lossy(record).lines() {
+        let fields = record_fields(line);
+        let [path, digest, ..] = fields.as_slice() else {
+            continue;
+        };
+        let Some(bytes) = unlisted.remove(path.as_str()) else {
+            eprintln!(
+                "[WARN] File listed in wheel RECORD missing from package: {}",
+                context.join(path).display()
+            );
+            continue;
+        };
+
+        // The RECORD cannot list its own digest, nor those of any signatures of it
+        let Some((algo, expected)) = digest.split_once('=') else {
+            continue;
+        };
+        let Some(bytes) = bytes else {
+            warn_too_large(&context.join(path));
+            continue;
+        };
+        match wheel_digest(algo, bytes) {
+            Some(computed) if computed != expected => eprintln!(
+                "[WARN] Digest mismatch (possible tampering): {} (expected {}, computed {algo}={computed})",
+                context.join(path).display(),
+                digest,
+            ),
+            Some(_) => {}
+            None => eprintln!(
+                "[WARN] Unsupported wheel RECORD digest algorithm ({algo}); skipping verification: {}",
+                context.join(path).display()
+            ),
+        }
+    }
+
+    for (path, _) in files {
+        if unlisted.contains_key(path.as_str()) {
+            eprintln!(
+                "[WARN] File not listed in wheel RECORD: {}",
+                context.join(path).display()
+            );
+        }
+    }
+}
+
+fn gem_digest(algo: &str, data: &[u8]) -> Option<String> {
+    match algo {
+        "SHA1" => Some(hash::compute_hash::<Sha1>(data)),
+        "SHA256" => Some(hash::compute_hash::<Sha256>(data)),
+        "SHA512" => Some(hash::compute_hash::<Sha512>(data)),
+        _ => None,
+    }
+}
+
+// Compare the digests of a gem's metadata and data against those in its checksums.  The
+// checksums are a YAML mapping of each algorithm to a mapping of each file to its digest,
+// which is simple enough that we read it line by line
+fn verify_gem_checksums(entries: &[ArchiveEntry], checksums: &[u8], context: &Path) {
+    let mut yaml = String::new();
+    if let Err(e) = GzDecoder::new(checksums).read_to_string(&mut yaml) {
+        eprintln!(
+            "[WARN] Failed to read gem checksums ({e}): {}",
+            context.join(GEM_CHECKSUMS).display()
+        );
+        return;
+    }
+
+    let mut algo = None;
+    for line in yaml.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(['\'', '"']);
+
+        // Algorithms are top-level keys, and files are indented beneath them
+        if !line.starts_with(' ') {
+            algo = Some(key.trim());
+            continue;
+        }
+        let (Some(algo), name) = (algo, key.trim()) else {
+            continue;
+        };
+
+        let Some(EntryData::File(file)) = find_entry(entries, name).map(|e| &e.data) else {
+            eprintln!(
+                "[WARN] File listed in gem checksums missing from package: {}",
+                context.join(name).display()
+            );
+            continue;
+        };
+        let Some(bytes) = &file.bytes else {
+            warn_too_large(&context.join(name));
+            continue;
+        };
+        match gem_digest(algo, bytes) {
+            Some(computed) if computed != value.to_lowercase() => eprintln!(
+                "[WARN] Digest mismatch (possible tampering): {} (expected {}, computed {})",
+                context.join(name).display(),
+                value,
+                computed
+            ),
+            Some(_) => {}
+            None => eprintln!(
+                "[WARN] Unsupported gem checksum algorithm ({algo}); skipping verification: {}",
+                context.join(name).display()
+            ),
+        }
+    }
+}
+
+// Verify the (tree-structured) archive's contents against the package's own record of
+// their digests, if it is a package with one
+pub fn verify_package(entries: &[ArchiveEntry], context: &Path) {
+    let record = entries
+        .iter()
+        .filter(|entry| entry.name().ends_with(WHEEL_METADATA_DIR_SUFFIX))
+        .map(|entry| format!("{}/{WHEEL_RECORD}", entry.name()))
+        .find_map(|path| Some((find_entry(entries, &path)?, path)));
+    if let Some((entry, path)) = record
+        && let EntryData::File(file) = &entry.data
+    {
+        match &file.bytes {
+            Some(record) => verify_wheel_record(entries, record, context),
+            None => warn_too_large(&context.join(path)),
+        }
+    }
+
+    if let Some(EntryData::File(file)) = find_entry(entries, GEM_CHECKSUMS).map(|e| &e.data) {
+        match &file.bytes {
+            Some(checksums) => verify_gem_checksums(entries, checksums, context),
+            None => warn_too_large(&context.join(GEM_CHECKSUMS)),
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Handle PDF embedded files
+//!
+//! Read files embedded in PDF documents, from both the document's embedded files name tree (as used for attachments and portfolios) and file attachment annotations on its pages.  Embedded file streams are decoded (usually from FlateDecode) in memory, and named after their file specification.  Archives embedded in a document are expanded by recursion (see [`recurse`](crate::recurse)).  NOTE: we can only read encrypted documents which have no user password (i.e., those which open without a password)
+//!
+//! Attachments from annotations are listed in a directory for their page, as they need not have unique names.
+//!
+//! Format reference:
+//!   <opensource.adobe.com/dc-acrobat-sdk-docs/pdfstandards/PDF32000_2008.pdf>
+
+use super::ArchiveHandler;
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use lopdf::{Dictionary, Document, Object, ObjectId, decode_text_string};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+// Name trees are balanced trees, but a malformed (or malicious) document may have
+// cycles, or be very deep
+const MAX_NAME_TREE_DEPTH: usize = 32;
+
+struct EmbeddedFiles<'a> {
+    document: &'a Document,
+    files: Vec<ArchiveEntry>,
+    // The same file specification may be referenced from both the name tree and an
+    // annotation
+    seen: HashSet<ObjectId>,
+    n_unnamed: usize,
+}
+
+impl<'a> EmbeddedFiles<'a> {
+    fn new(document: &'a Document) -> Self {
+        Self {
+            document,
+            files: Vec::new(),
+            seen: HashSet::new(),
+            n_unnamed: 0,
+        }
+    }
+
+    fn deref(&self, object: &'a Object) -> Option<&'a Object> {
+        self.document.dereference(object).ok().map(|(_, o)| o)
+    }
+
+    fn deref_dict(&self, object: &'a Object) -> Option<&'a Dictionary> {
+        self.deref(object).and_then(|o| o.as_dict().ok())
+    }
+
+    // File specifications name their file in several keys, of which the Unicode one is
+    // preferred.  Names cannot be trusted to not contain path separators
+    fn file_name(&self, spec: &Dictionary) -> Option<String> {
+        [b"UF".as_slice(), b"F", b"DOS", b"Unix", b"Mac"]
+            .iter()
+            .filter_map(|key| spec.get(key).ok().and_then(|o| self.deref(o)))
+            .find_map(|o| decode_text_string(o).ok().filter(|name| !name.is_empty()))
+            .map(|name| name.replace(['/', '\\'], "_"))
+    }
+
+    // Add an entry for the file embedded in a file specification (if it has one; file
+    // specifications may instead refer to an external file)
+    fn add_file_spec(&mut self, spec: &'a Object, fallback_name: Option<String>, dir: &Path) {
+        let Some(spec_dict) = self.deref_dict(spec) else {
+            return;
+        };
+        let Some(embedded) = spec_dict.get(b"EF").ok().and_then(|o| self.deref_dict(o)) else {
+            return;
+        };
+        let Some(stream_ref) = [b"UF".as_slice(), b"F"]
+            .iter()
+            .find_map(|key| embedded.get(key).ok())
+        else {
+            return;
+        };
+
+        if let Ok(id) = stream_ref.as_reference()
+            && !self.seen.insert(id)
+        {
+            return;
+        }
+
+        let name = self
+            .file_name(spec_dict)
+            .or(fallback_name)
+            .unwrap_or_else(|| {
+                self.n_unnamed += 1;
+                format!("attachment-{}", self.n_unnamed)
+            });
+        let path = dir.join(name);
+
+        let stream = match self.deref(stream_ref).map(Object::as_stream) {
+            Some(Ok(stream)) => stream,
+            _ => {
+                eprintln!(
+                    "[WARN] Skipping embedded file (missing stream): {}",
+                    path.display()
+                );
+                return;
+            }
+        };
+        match stream.decompressed_content() {
+            Ok(bytes) => self.files.push(ArchiveEntry {
+                path: Some(path),
+                data: EntryData::File(FileData::from_bytes(bytes)),
+            }),
+            Err(e) => eprintln!("[WARN] Skipping embedded file ({e}): {}", path.display()),
+        }
+    }
+
+    // Walk the embedded files name tree, whose leaves map names to file specifications
+    fn add_name_tree(&mut self, node: &'a Object, depth: usize) {
+        let Some(node) = self.deref_dict(node) else {
+            return;
+        };
+        if depth > MAX_NAME_TREE_DEPTH {
+            eprintln!("[WARN] Skipping embedded files (name tree too deep)");
+            return;
+        }
+
+        if let Some(names) = node
+            .get(b"Names")
+            .ok()
+            .and_then(|o| self.deref(o))
+            .and_then(|o| o.as_array().ok())
+        {
+            for pair in names.chunks_exact(2) {
+                let key = self
+                    .deref(&pair[0])
+                    .and_then(|o| decode_text_string(o).ok())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| name.replace(['/', '\\'], "_"));
+                self.add_file_spec(&pair[1], key, Path::new(""));
+            }
+        }
+
+        if let Some(kids) = node
+            .get(b"Kids")
+            .ok()
+            .and_then(|o| self.deref(o))
+            .and_then(|o| o.as_array().ok())
+        {
+            for kid in kids {
+                self.add_name_tree(kid, depth + 1);
+            }
+        }
+    }
+
+    fn add_document_files(&mut self) {
+        let tree = self
+            .document
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"Names").ok())
+            .and_then(|o| self.deref_dict(o))
+            .and_then(|names| names.get(b"EmbeddedFiles").ok());
+        if let Some(tree) = tree {
+            self.add_name_tree(tree, 0);
+        }
+    }
+
+    fn add_annotation_files(&mut self) {
+        for (page_number, page_id) in self.document.get_pages() {
+            let dir = PathBuf::from(format!("page-{page_number}"));
+            let Ok(annotations) = self.document.get_page_annotations(page_id) else {
+                continue;
+            };
+            for annotation in annotations {
+                let is_file_attachment = annotation
+                    .get(b"Subtype")
+                    .and_then(Object::as_name)
+                    .is_ok_and(|subtype| subtype == b"FileAttachment");
+                if is_file_attachment && let Ok(spec) = annotation.get(b"FS") {
+                    self.add_file_spec(spec, None, &dir);
+                }
+            }
+        }
+    }
+}
+
+fn get_embedded_files(bytes: &[u8]) -> Result<Vec<ArchiveEntry>, String> {
+    let document = Document::load_mem(bytes).map_err(|e| e.to_string())?;
+
+    // Documents with no user password are decrypted when loaded
+    if document.is_encrypted() {
+        return Err(String::from("encrypted document requires a password"));
+    }
+
+    let mut embedded = EmbeddedFiles::new(&document);
+    embedded.add_document_files();
+    embedded.add_annotation_files();
+    Ok(embedded.files)
+}
+
+pub struct Pdf;
+
+impl ArchiveHandler for Pdf {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Pdf
+    }
+
+    fn name(&self) -> &'static str {
+        "pdf"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/pdf"]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        // Most documents do not have files embedded
+        match get_embedded_files(bytes) {
+            Ok(files) if files.is_empty() => origin.no_entries("No embedded files found in PDF"),
+            Ok(files) => Some(files),
+            Err(e) => origin.read_error("PDF", e),
+        }
+    }
+}
@@ -1,116 +1,85 @@
 //! Handle rar archive format
 //!
 //! Read archive files from (potentially password-protected) rar files
+//!
+//! RAR 5.0 archives (the default format since WinRAR 5.0) are read natively, from a buffer (see [`rar5`](super::rar5)).  Older archives are read with the RAR library (see [`rar4`](super::rar4)), if built with the `unrar` feature
 
-use crate::archive::{ArchiveEntry, EntryData};
-use rpassword::prompt_password;
-use std::{path::Path, process};
-use unrar::{
-    error::{Code, UnrarError},
-    {Archive, CursorBeforeHeader, OpenArchive, Process},
+#[cfg(feature = "unrar")]
+use super::rar4;
+use super::{
+    ArchiveHandler,
+    rar5::{self, RAR5_MAGIC},
+};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, Origin},
+    file, volume,
 };
+use std::{io, iter};
 
-type RarArchive = OpenArchive<Process, CursorBeforeHeader>;
+pub struct Rar;
 
-// The problem is, RAR supports both fully encrypted files, or files whose listings
-// or header information are available but whose file contents are not.
-//   - Fully encrypted: github.com/muja/unrar.rs/raw/0628d12b/data/comment-hpw-password.rar
-//   - Partially encrypted: github.com/muja/unrar.rs/raw/0628d12b/data/crypted.rar
-//   - Expected behaviour: github.com/muja/unrar.rs/blob/0628d12b/tests/crypted.rs
-fn is_encrypted(path: &String) -> bool {
-    let arc = Archive::new(path).open_for_processing().unwrap();
-    match arc.read_header() {
-        // Case 1: the content and headers are fully encrypted
-        Err(e) if e.code == Code::MissingPassword => true,
-        Ok(Some(header)) => {
-            // We may be able to open the header but not read the contents as it may
-            // only be partially encrypted
-            matches!(header.read(), Err(e) if e.code == Code::MissingPassword)
-        }
-        _ => false,
+impl ArchiveHandler for Rar {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Rar
     }
-}
 
-fn try_decrypt_from_rar_archive(path: &String) -> RarArchive {
-    let password = prompt_password("Enter password: ").unwrap();
-    Archive::with_password(path, &password)
-        .open_for_processing()
-        .unwrap()
-}
+    fn name(&self) -> &'static str {
+        "rar"
+    }
 
-fn handle_rar_error(e: UnrarError, path: &String, prompted: bool) -> RarArchive {
-    match e.code {
-        Code::MissingPassword if !prompted => try_decrypt_from_rar_archive(path),
-        Code::MissingPassword | Code::BadPassword => {
-            eprintln!("[ERROR] Incorrect password");
-            process::exit(1);
-        }
-        Code::BadData => {
-            if is_encrypted(path) {
-                eprintln!("[ERROR] Incorrect password");
-            } else {
-                eprintln!("[ERROR] Error reading RAR archive: {}", e);
-            }
-            process::exit(1);
-        }
-        _ => {
-            eprintln!("[ERROR] Error reading RAR archive: {}", e);
-            process::exit(1);
-        }
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/vnd.rar"]
     }
-}
 
-pub fn get_files_from_rar_archive(path: &String) -> Vec<ArchiveEntry> {
-    let mut files = Vec::new();
-    let mut archive = Archive::new(path).open_for_processing().unwrap();
-    let mut prompted = false;
+    // Multi-volume archives (".part1.rar", ".part2.rar", ...) are read volume by volume,
+    // given the first
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        if !bytes.starts_with(RAR5_MAGIC) {
+            return get_files_from_rar4(bytes, origin);
+        }
 
-    loop {
-        match archive.read_header() {
-            Err(e) => {
-                archive = handle_rar_error(e, path, prompted);
-                prompted = true;
-            }
-            Ok(None) => break,
-            Ok(Some(header)) => {
-                let is_dir = header.entry().is_directory();
-                let filename = header.entry().filename.clone();
+        // Only the input file can have further volumes beside it
+        let rest = match origin {
+            Origin::Input(path) => volume::find_rar_volumes(path)
+                .into_iter()
+                .flatten()
+                .skip(1)
+                .map(|volume| file::map_file(&volume))
+                .collect::<io::Result<Vec<_>>>(),
+            Origin::Nested { .. } => Ok(Vec::new()),
+        };
+        let rest = match rest {
+            Ok(rest) => rest,
+            Err(e) => return origin.read_error("RAR volume", e),
+        };
+        let volumes = iter::once(bytes)
+            .chain(rest.iter().map(|volume| &volume[..]))
+            .collect::<Vec<_>>();
 
-                if is_dir {
-                    archive = header.skip().unwrap();
-                    files.push(ArchiveEntry {
-                        path: Some(filename),
-                        data: EntryData::Directory(Vec::new()),
-                    });
-                } else {
-                    match header.read() {
-                        Err(e) => {
-                            archive = handle_rar_error(e, path, prompted);
-                            prompted = true;
-                        }
-                        Ok((bytes, rest)) => {
-                            archive = rest;
-                            files.push(ArchiveEntry {
-                                path: Some(filename),
-                                data: EntryData::File(bytes),
-                            });
-                        }
-                    }
-                }
-            }
+        let mut get_password = || origin.password();
+        match rar5::get_files_from_rar5_volumes(&volumes, &mut get_password) {
+            Ok(files) => Some(files),
+            // We were not given a password, having already warned why
+            Err(rar5::Error::MissingPassword) => None,
+            Err(rar5::Error::IncorrectPassword) => origin.incorrect_password(),
+            Err(e) => origin.read_error("RAR archive", e),
         }
     }
+}
 
-    files
+// The RAR library reads the input archive from its path, but nested archives from memory
+#[cfg(feature = "unrar")]
+fn get_files_from_rar4(bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+    match origin {
+        Origin::Input(path) => rar4::get_files_from_rar4_archive(path),
+        Origin::Nested { .. } => rar4::get_files_from_rar4_bytes(bytes, origin),
+    }
 }
 
-// Reading a RAR file from a byte stream is intentionally not supported due to limitations
-// in the underlying C code:
-//   https://github.com/muja/unrar.rs/blob/0628d12b/README.md#L151
-pub fn get_files_from_rar_bytes(
-    bytes: Vec<u8>,
-    _allow_password_prompt: bool,
-    _context: &Path,
-) -> Result<Vec<ArchiveEntry>, Vec<u8>> {
-    Err(bytes)
+#[cfg(not(feature = "unrar"))]
+fn get_files_from_rar4(_bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+    origin.read_error(
+        "RAR archive",
+        "RAR 4 archives are unsupported without the unrar feature",
+    )
 }
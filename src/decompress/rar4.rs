@@ -0,0 +1,152 @@
+//! Handle RAR 4 (and earlier) archives
+//!
+//! Read archive files from (potentially password-protected) RAR archives older than RAR 5.0, with the RAR library.  This requires the `unrar` feature (on by default), as the library is written in C++.  The library also locates and reads the subsequent volumes of multi-volume archives itself
+//!
+//! The RAR library can only read archives from a path.  On Linux, nested RAR archives are given to it as an anonymous in-memory file (see `memfd_create(2)`), whose path is under `/proc/self/fd`, so that we still never write to disk.  Elsewhere, nested RAR archives are not expanded
+
+use crate::archive::{ArchiveEntry, EntryData, FileData, Origin};
+#[cfg(target_os = "linux")]
+use memfd::MemfdOptions;
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+#[cfg(target_os = "linux")]
+use std::{io::Write, os::fd::AsRawFd};
+use unrar::{
+    error::{Code, UnrarError},
+    {Archive, CursorBeforeHeader, OpenArchive, Process},
+};
+
+type RarArchive = OpenArchive<Process, CursorBeforeHeader>;
+
+// The problem is, RAR supports both fully encrypted files, or files whose listings
+// or header information are available but whose file contents are not.
+//   - Fully encrypted: github.com/muja/unrar.rs/raw/0628d12b/data/comment-hpw-password.rar
+//   - Partially encrypted: github.com/muja/unrar.rs/raw/0628d12b/data/crypted.rar
+//   - Expected behaviour: github.com/muja/unrar.rs/blob/0628d12b/tests/crypted.rs
+fn is_encrypted(path: &Path) -> bool {
+    let Ok(arc) = Archive::new(path).open_for_processing() else {
+        return false;
+    };
+    match arc.read_header() {
+        // Case 1: the content and headers are fully encrypted
+        Err(e) if e.code == Code::MissingPassword => true,
+        Ok(Some(header)) => {
+            // We may be able to open the header but not read the contents as it may
+            // only be partially encrypted
+            matches!(header.read(), Err(e) if e.code == Code::MissingPassword)
+        }
+        _ => false,
+    }
+}
+
+fn try_decrypt_from_rar_archive(path: &Path, origin: Origin) -> Option<RarArchive> {
+    let password = origin.password()?;
+    Archive::with_password(path, &password)
+        .open_for_processing()
+        .map_or_else(|e| origin.read_error("RAR archive", e), Some)
+}
+
+fn handle_rar_error(
+    e: UnrarError,
+    path: &Path,
+    origin: Origin,
+    prompted: bool,
+) -> Option<RarArchive> {
+    match e.code {
+        Code::MissingPassword if !prompted => try_decrypt_from_rar_archive(path, origin),
+        Code::MissingPassword | Code::BadPassword => origin.incorrect_password(),
+        Code::BadData if is_encrypted(path) => origin.incorrect_password(),
+        _ => origin.read_error("RAR archive", e),
+    }
+}
+
+// Read every entry of the archive at the given path.  `origin` names the archive in
+// password prompts, if it is nested within another
+fn read_rar_archive(path: &Path, origin: Origin) -> Option<Vec<ArchiveEntry>> {
+    let mut files = Vec::new();
+    let mut archive = match Archive::new(path).open_for_processing() {
+        Ok(archive) => archive,
+        Err(e) => return origin.read_error("RAR archive", e),
+    };
+    let mut prompted = false;
+
+    loop {
+        match archive.read_header() {
+            Err(e) => {
+                archive = handle_rar_error(e, path, origin, prompted)?;
+                prompted = true;
+            }
+            Ok(None) => break,
+            Ok(Some(header)) => {
+                let is_dir = header.entry().is_directory();
+                let filename = header.entry().filename.clone();
+
+                if is_dir {
+                    archive = match header.skip() {
+                        Ok(rest) => rest,
+                        Err(e) => return origin.read_error("RAR archive", e),
+                    };
+                    files.push(ArchiveEntry {
+                        path: Some(filename),
+                        data: EntryData::Directory(Vec::new()),
+                    });
+                } else {
+                    match header.read() {
+                        Err(e) => {
+                            archive = handle_rar_error(e, path, origin, prompted)?;
+                            prompted = true;
+                        }
+                        Ok((bytes, rest)) => {
+                            archive = rest;
+                            files.push(ArchiveEntry {
+                                path: Some(filename),
+                                data: EntryData::File(FileData::from_bytes(bytes)),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Some(files)
+}
+
+// The RAR library reads the input archive (and finds any further volumes) from its path
+pub fn get_files_from_rar4_archive(path: &Path) -> Option<Vec<ArchiveEntry>> {
+    read_rar_archive(path, Origin::Input(path))
+}
+
+// Returns a vector of archive entries pertaining to each file, from buffer
+//
+// This is used for recursing into nested archives.  Reading a RAR file from a byte
+// stream is not supported by the underlying C library, so we give it the path of an
+// anonymous in-memory file holding the buffer instead:
+//   https://github.com/muja/unrar.rs/blob/0628d12b/README.md#L151
+#[cfg(target_os = "linux")]
+pub fn get_files_from_rar4_bytes(bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+    let memfd = match MemfdOptions::default().create("xmemhash-rar") {
+        Ok(memfd) => memfd,
+        Err(e) => return origin.read_error("RAR archive", e),
+    };
+    if let Err(e) = memfd.as_file().write_all(bytes) {
+        return origin.read_error("RAR archive", e);
+    }
+
+    // The file descriptor stays open (and so the file exists) until `memfd` is dropped
+    let path = PathBuf::from(format!("/proc/self/fd/{}", memfd.as_raw_fd()));
+    read_rar_archive(&path, origin)
+}
+
+// Reading a RAR file from a byte stream is intentionally not supported due to limitations
+// in the underlying C code, and outside of Linux we have no way to give it a path to
+// memory without writing to disk:
+//   https://github.com/muja/unrar.rs/blob/0628d12b/README.md#L151
+#[cfg(not(target_os = "linux"))]
+pub fn get_files_from_rar4_bytes(_bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+    origin.read_error(
+        "RAR archive",
+        "RAR 4 recursion unsupported without writing to disk",
+    )
+}
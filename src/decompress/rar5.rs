@@ -0,0 +1,1159 @@
+//! Handle RAR 5.0 archives natively
+//!
+//! Read RAR 5.0 archives (the format written by WinRAR 5.0 and later) from a buffer, without the RAR library.  Each archive is a sequence of headers (some followed by data): a main header, one for each file, service headers (such as for comments or the recovery record, which we skip), and an end of archive header.  Files are either stored or compressed with the RAR 5.0 algorithm, which is LZ77 with Huffman coding, and filters (delta, x86 and ARM) which make some data more compressible.  Solid archives compress each file as a continuation of the one before, so every file is decompressed in order.  Multi-volume archives are given as a list of volumes, in order, and files split between them are joined before decompression.  NOTE: both files and headers may be encrypted, with AES-256 in CBC mode, keyed with PBKDF2-HMAC-SHA256
+//!
+//! Files are verified against their CRC32, where the archive records one (files with only a BLAKE2sp hash, as written with `-htb`, are not verified).
+//!
+//! Format references:
+//!   <www.rarlab.com/technote.htm>
+//!   <www.rarlab.com/rar_add.htm> (UnRAR source: `unpack50.cpp`, `crypt5.cpp`)
+
+use crate::archive::{ArchiveEntry, EntryData, FileData};
+use aes::{
+    Aes256,
+    cipher::{BlockDecrypt, KeyInit, generic_array::GenericArray},
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{fmt, ops::Range, path::PathBuf};
+
+pub const RAR5_MAGIC: &[u8] = b"Rar!\x1a\x07\x01\x00";
+
+// Header types
+const HEAD_MAIN: u64 = 1;
+const HEAD_FILE: u64 = 2;
+const HEAD_CRYPT: u64 = 4;
+const HEAD_END: u64 = 5;
+
+// Flags common to all headers
+const HFL_EXTRA: u64 = 0x0001;
+const HFL_DATA: u64 = 0x0002;
+const HFL_SPLIT_BEFORE: u64 = 0x0008;
+const HFL_SPLIT_AFTER: u64 = 0x0010;
+
+// Main, file, end of archive, and encryption header flags
+const MHFL_VOLUME_NUMBER: u64 = 0x0002;
+const FHFL_DIRECTORY: u64 = 0x0001;
+const FHFL_UNIX_TIME: u64 = 0x0002;
+const FHFL_CRC32: u64 = 0x0004;
+const FHFL_UNKNOWN_SIZE: u64 = 0x0008;
+const EHFL_NEXT_VOLUME: u64 = 0x0001;
+const CHFL_PASSWORD_CHECK: u64 = 0x0001;
+
+// File header extra records, and their flags
+const FHEXTRA_CRYPT: u64 = 0x01;
+const FHEXTRA_REDIR: u64 = 0x05;
+const FHEXTRA_CRYPT_PASSWORD_CHECK: u64 = 0x01;
+const FHEXTRA_CRYPT_HASH_MAC: u64 = 0x02;
+
+// Redirection (link) types
+const REDIR_UNIX_SYMLINK: u64 = 1;
+const REDIR_WINDOWS_SYMLINK: u64 = 2;
+const REDIR_JUNCTION: u64 = 3;
+const REDIR_HARD_LINK: u64 = 4;
+const REDIR_FILE_COPY: u64 = 5;
+
+// Compression information: algorithm version, solid flag, method (0 is stored), and
+// dictionary size.  Version 1 is RAR 7.0's, which allows larger dictionaries and
+// distances, unless the archive is marked as compatible with RAR 5.0
+const FCI_VERSION_MASK: u64 = 0x3f;
+const FCI_SOLID: u64 = 0x40;
+const FCI_RAR5_COMPAT: u64 = 0x0010_0000;
+const MIN_DICTIONARY_SIZE: u64 = 0x20000;
+const MAX_DICTIONARY_SHIFT: u64 = 20;
+
+// Encryption parameters
+const CRYPT_VERSION: u64 = 0;
+const KDF_MAX_LOG2_COUNT: u8 = 24;
+const SALT_SIZE: usize = 16;
+const IV_SIZE: usize = 16;
+const PASSWORD_CHECK_SIZE: usize = 8;
+const PASSWORD_CHECK_SUM_SIZE: usize = 4;
+const AES_BLOCK_SIZE: usize = 16;
+
+// Huffman alphabets: bit lengths; literals, filters, repeats, and lengths; distances
+// (extended for RAR 7.0); low distance bits; and repeated lengths
+const BC: usize = 20;
+const NC: usize = 306;
+const DCB: usize = 64;
+const DCX: usize = 80;
+const LDC: usize = 16;
+const RC: usize = 44;
+
+// Filters
+const FILTER_DELTA: u8 = 0;
+const FILTER_E8: u8 = 1;
+const FILTER_E8E9: u8 = 2;
+const FILTER_ARM: u8 = 3;
+const MAX_FILTER_BLOCK_SIZE: usize = 0x400000;
+const E8_FILE_SIZE: u32 = 0x1000000;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub enum Error {
+    // A password is required, but none was given
+    MissingPassword,
+    IncorrectPassword,
+    // The archive continues in a volume we were not given
+    MissingVolume,
+    Unsupported(String),
+    Malformed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingPassword => write!(f, "missing password"),
+            Error::IncorrectPassword => write!(f, "incorrect password"),
+            Error::MissingVolume => write!(f, "next volume not found"),
+            Error::Unsupported(what) => write!(f, "unsupported {what}"),
+            Error::Malformed(what) => write!(f, "malformed archive ({what})"),
+        }
+    }
+}
+
+fn malformed(what: &str) -> Error {
+    Error::Malformed(String::from(what))
+}
+
+// Header fields are mostly variable-length integers, seven bits to a byte (least
+// significant first), whose high bit is set if more bytes follow
+struct Fields<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos.saturating_add(n))
+            .ok_or_else(|| malformed("truncated header"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn vint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(malformed("overlong integer"))
+    }
+
+    fn usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(self.vint()?).map_err(|_| malformed("size too large"))
+    }
+
+    fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.buf[self.pos.min(self.buf.len())..];
+        self.pos = self.buf.len();
+        rest
+    }
+}
+
+struct Block {
+    kind: u64,
+    flags: u64,
+    // The header's type-specific fields, followed by its extra area
+    body: Vec<u8>,
+    extra_start: usize,
+    // The range of the volume holding the header's data
+    data: Range<usize>,
+}
+
+impl Block {
+    fn fields(&self) -> Fields<'_> {
+        Fields::new(&self.body[..self.extra_start])
+    }
+
+    // Extra areas are a list of records, each of a size, a type, and its data
+    fn extra_records(&self) -> Result<Vec<(u64, Fields<'_>)>, Error> {
+        let mut extra = Fields::new(&self.body[self.extra_start..]);
+        let mut records = Vec::new();
+        while extra.pos < extra.buf.len() {
+            let size = extra.usize()?;
+            let mut record = Fields::new(extra.bytes(size)?);
+            records.push((record.vint()?, record));
+        }
+        Ok(records)
+    }
+}
+
+// Key derivation parameters, for both encrypted headers and files
+#[derive(Clone, Copy, PartialEq)]
+struct Kdf {
+    log2_count: u8,
+    salt: [u8; SALT_SIZE],
+    password_check: Option<[u8; PASSWORD_CHECK_SIZE]>,
+}
+
+impl Kdf {
+    fn read(fields: &mut Fields) -> Result<Self, Error> {
+        let log2_count = fields.u8()?;
+        if log2_count > KDF_MAX_LOG2_COUNT {
+            return Err(Error::Unsupported(format!(
+                "key derivation iteration count (2^{log2_count})"
+            )));
+        }
+        Ok(Self {
+            log2_count,
+            salt: fields.array()?,
+            password_check: None,
+        })
+    }
+
+    // The password check value is itself checked with the start of its SHA-256, so that
+    // a damaged value is not mistaken for an incorrect password
+    fn read_password_check(&mut self, fields: &mut Fields) -> Result<(), Error> {
+        let check: [u8; PASSWORD_CHECK_SIZE] = fields.array()?;
+        let sum = fields.bytes(PASSWORD_CHECK_SUM_SIZE)?;
+        self.password_check =
+            (Sha256::digest(check)[..PASSWORD_CHECK_SUM_SIZE] == *sum).then_some(check);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Keys {
+    key: [u8; 32],
+    // Keys the checksums of encrypted files, so that they reveal nothing of the data
+    hash_key: [u8; 32],
+}
+
+// PBKDF2-HMAC-SHA256, continued for 16 and 32 more iterations to give the key for
+// checksums and the password check value respectively
+fn derive_keys(password: &str, kdf: &Kdf) -> Result<Keys, Error> {
+    let mac = <HmacSha256 as Mac>::new_from_slice(password.as_bytes()).unwrap();
+    let mut u = mac
+        .clone()
+        .chain_update(kdf.salt)
+        .chain_update(1u32.to_be_bytes())
+        .finalize()
+        .into_bytes();
+    let mut f = u;
+
+    let mut values = [[0u8; 32]; 3];
+    for (value, iterations) in values
+        .iter_mut()
+        .zip([(1u64 << kdf.log2_count) - 1, 16, 16])
+    {
+        for _ in 0..iterations {
+            u = mac.clone().chain_update(u).finalize().into_bytes();
+            f.iter_mut().zip(&u).for_each(|(f, u)| *f ^= u);
+        }
+        value.copy_from_slice(&f);
+    }
+    let [key, hash_key, check_value] = values;
+
+    if let Some(expected) = kdf.password_check {
+        let mut check = [0u8; PASSWORD_CHECK_SIZE];
+        for (i, byte) in check_value.iter().enumerate() {
+            check[i % PASSWORD_CHECK_SIZE] ^= byte;
+        }
+        if check != expected {
+            return Err(Error::IncorrectPassword);
+        }
+    }
+
+    Ok(Keys { key, hash_key })
+}
+
+// AES-256 in CBC mode.  Encrypted data is padded to the block size, so any incomplete
+// block at the end is ignored
+fn decrypt(key: &[u8; 32], iv: &[u8; IV_SIZE], data: &mut [u8]) {
+    let cipher = Aes256::new(GenericArray::from_slice(key));
+    let mut previous = *iv;
+    for block in data.chunks_exact_mut(AES_BLOCK_SIZE) {
+        let ciphertext: [u8; AES_BLOCK_SIZE] = (*block).try_into().unwrap();
+        cipher.decrypt_block(GenericArray::from_mut_slice(block));
+        block.iter_mut().zip(previous).for_each(|(b, p)| *b ^= p);
+        previous = ciphertext;
+    }
+}
+
+// Checksums of encrypted files are replaced by an HMAC of them, folded to 32 bits
+fn crc32_to_mac(crc: u32, hash_key: &[u8; 32]) -> u32 {
+    let digest = <HmacSha256 as Mac>::new_from_slice(hash_key)
+        .unwrap()
+        .chain_update(crc.to_le_bytes())
+        .finalize()
+        .into_bytes();
+    digest
+        .iter()
+        .enumerate()
+        .fold(0, |mac, (i, &b)| mac ^ u32::from(b) << ((i & 3) * 8))
+}
+
+// Read the header at the given position of the volume.  Once headers are encrypted,
+// each is preceded by its own initialisation vector, and padded to the block size
+fn read_block(
+    volume: &[u8],
+    pos: usize,
+    header_key: Option<&[u8; 32]>,
+) -> Result<(Block, usize), Error> {
+    let truncated = || malformed("truncated header");
+
+    let (header, next) = match header_key {
+        Some(key) => {
+            let iv: [u8; IV_SIZE] = volume
+                .get(pos..pos + IV_SIZE)
+                .ok_or_else(truncated)?
+                .try_into()
+                .unwrap();
+            let start = pos + IV_SIZE;
+
+            // Decrypt the first block to find the header's size
+            let mut first = volume
+                .get(start..start + AES_BLOCK_SIZE)
+                .ok_or_else(truncated)?
+                .to_vec();
+            decrypt(key, &iv, &mut first);
+            let mut fields = Fields::new(&first[4..]);
+            let size = fields.usize()?;
+            let len = (4 + fields.pos)
+                .checked_add(size)
+                .ok_or_else(truncated)?
+                .next_multiple_of(AES_BLOCK_SIZE);
+
+            let mut header = volume
+                .get(start..start.saturating_add(len))
+                .ok_or_else(truncated)?
+                .to_vec();
+            decrypt(key, &iv, &mut header);
+            header.truncate(4 + fields.pos + size);
+            (header, start + len)
+        }
+        None => {
+            let mut fields = Fields::new(volume.get(pos + 4..).ok_or_else(truncated)?);
+            let size = fields.usize()?;
+            let end = (pos + 4 + fields.pos).saturating_add(size);
+            (volume.get(pos..end).ok_or_else(truncated)?.to_vec(), end)
+        }
+    };
+
+    // The checksum covers the header from its size onwards.  Where the header is
+    // encrypted, a mismatch is most likely due to an incorrect password
+    let crc = u32::from_le_bytes(header[..4].try_into().unwrap());
+    if crc32fast::hash(&header[4..]) != crc {
+        return Err(match header_key {
+            Some(_) => Error::IncorrectPassword,
+            None => malformed("header checksum mismatch"),
+        });
+    }
+
+    let mut fields = Fields::new(&header[4..]);
+    fields.vint()?;
+    let kind = fields.vint()?;
+    let flags = fields.vint()?;
+    let extra_size = if flags & HFL_EXTRA != 0 {
+        fields.usize()?
+    } else {
+        0
+    };
+    let data_size = if flags & HFL_DATA != 0 {
+        fields.usize()?
+    } else {
+        0
+    };
+    let body = fields.rest().to_vec();
+    let extra_start = body
+        .len()
+        .checked_sub(extra_size)
+        .ok_or_else(|| malformed("extra area exceeds header"))?;
+
+    let data = next..next.saturating_add(data_size);
+    if data.end > volume.len() {
+        return Err(malformed("truncated data"));
+    }
+
+    let block = Block {
+        kind,
+        flags,
+        body,
+        extra_start,
+        data: data.clone(),
+    };
+    Ok((block, data.end))
+}
+
+struct FileEncryption {
+    kdf: Kdf,
+    iv: [u8; IV_SIZE],
+    use_mac: bool,
+}
+
+struct FileHeader {
+    name: String,
+    is_dir: bool,
+    unpacked_size: Option<u64>,
+    crc: Option<u32>,
+    compression: u64,
+    encryption: Option<FileEncryption>,
+    redirection: Option<(u64, String)>,
+}
+
+impl FileHeader {
+    fn read(block: &Block) -> Result<Self, Error> {
+        let mut fields = block.fields();
+        let flags = fields.vint()?;
+        let unpacked_size = fields.vint()?;
+        fields.vint()?; // Attributes
+        if flags & FHFL_UNIX_TIME != 0 {
+            fields.u32()?;
+        }
+        let crc = if flags & FHFL_CRC32 != 0 {
+            Some(fields.u32()?)
+        } else {
+            None
+        };
+        let compression = fields.vint()?;
+        fields.vint()?; // Host OS
+        let name_len = fields.usize()?;
+        let name = String::from_utf8_lossy(fields.bytes(name_len)?).into_owned();
+
+        let mut encryption = None;
+        let mut redirection = None;
+        for (kind, mut record) in block.extra_records()? {
+            match kind {
+                FHEXTRA_CRYPT => {
+                    let version = record.vint()?;
+                    if version != CRYPT_VERSION {
+                        return Err(Error::Unsupported(format!(
+                            "encryption version ({version})"
+                        )));
+                    }
+                    let flags = record.vint()?;
+                    let mut kdf = Kdf::read(&mut record)?;
+                    let iv = record.array()?;
+                    if flags & FHEXTRA_CRYPT_PASSWORD_CHECK != 0 {
+                        kdf.read_password_check(&mut record)?;
+                    }
+                    encryption = Some(FileEncryption {
+                        kdf,
+                        iv,
+                        use_mac: flags & FHEXTRA_CRYPT_HASH_MAC != 0,
+                    });
+                }
+                FHEXTRA_REDIR => {
+                    let redirection_type = record.vint()?;
+                    record.vint()?; // Flags
+                    let target_len = record.usize()?;
+                    let target = String::from_utf8_lossy(record.bytes(target_len)?).into_owned();
+                    redirection = Some((redirection_type, target));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            name,
+            is_dir: flags & FHFL_DIRECTORY != 0,
+            unpacked_size: (flags & FHFL_UNKNOWN_SIZE == 0).then_some(unpacked_size),
+            crc,
+            compression,
+            encryption,
+            redirection,
+        })
+    }
+
+    fn method(&self) -> u64 {
+        (self.compression >> 7) & 7
+    }
+
+    fn is_solid(&self) -> bool {
+        self.compression & FCI_SOLID != 0
+    }
+}
+
+// Compressed data is read most significant bit first
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    // Peek at up to 57 bits, reading zeros past the end of the data
+    fn peek(&self, n: usize) -> u64 {
+        let start = (self.pos / 8).min(self.data.len());
+        let available = &self.data[start..(start + 8).min(self.data.len())];
+        let mut word = [0u8; 8];
+        word[..available.len()].copy_from_slice(available);
+        (u64::from_be_bytes(word) << (self.pos % 8)) >> (64 - n)
+    }
+
+    fn read(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        let value = self.peek(n);
+        self.pos += n;
+        value as usize
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(8);
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.pos > self.data.len() * 8
+    }
+}
+
+// Canonical Huffman codes of up to 15 bits, given the bit length of each symbol
+struct Huffman {
+    // The (left-aligned) upper limit of the codes of each length, and the position of the
+    // first symbol of each length among the symbols sorted by code
+    decode_len: [u32; 16],
+    decode_pos: [u32; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut count = [0u32; 16];
+        for &length in lengths {
+            count[usize::from(length & 0xf)] += 1;
+        }
+        count[0] = 0;
+
+        let mut decode_len = [0u32; 16];
+        let mut decode_pos = [0u32; 16];
+        let mut upper_limit = 0;
+        for i in 1..16 {
+            upper_limit += count[i];
+            decode_len[i] = upper_limit << (16 - i);
+            upper_limit *= 2;
+            decode_pos[i] = decode_pos[i - 1] + count[i - 1];
+        }
+
+        let mut symbols = vec![0u16; lengths.len()];
+        let mut next_pos = decode_pos;
+        for (symbol, &length) in lengths.iter().enumerate() {
+            let length = usize::from(length & 0xf);
+            if length != 0 {
+                symbols[next_pos[length] as usize] = symbol as u16;
+                next_pos[length] += 1;
+            }
+        }
+
+        Self {
+            decode_len,
+            decode_pos,
+            symbols,
+        }
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> usize {
+        let code = (bits.peek(16) as u32) & 0xfffe;
+        let length = (1..15).find(|&i| code < self.decode_len[i]).unwrap_or(15);
+        bits.pos += length;
+
+        let offset = (code - self.decode_len[length - 1]) >> (16 - length);
+        let pos = (self.decode_pos[length] + offset) as usize;
+        usize::from(self.symbols.get(pos).copied().unwrap_or(self.symbols[0]))
+    }
+}
+
+struct Tables {
+    main: Huffman,
+    distance: Huffman,
+    low_distance: Huffman,
+    repeat_length: Huffman,
+}
+
+impl Tables {
+    // The bit lengths of each table are themselves Huffman coded, with codes to repeat
+    // the previous length, or to give a run of zeros
+    fn read(bits: &mut BitReader, extra_distances: bool) -> Result<Self, Error> {
+        let mut bit_lengths = [0u8; BC];
+        let mut i = 0;
+        while i < BC {
+            let length = bits.read(4) as u8;
+            if length == 15 {
+                match bits.read(4) {
+                    0 => {
+                        bit_lengths[i] = 15;
+                        i += 1;
+                    }
+                    zeros => i = (i + zeros + 2).min(BC),
+                }
+            } else {
+                bit_lengths[i] = length;
+                i += 1;
+            }
+        }
+        let bit_length_codes = Huffman::new(&bit_lengths);
+
+        let distance_codes = if extra_distances { DCX } else { DCB };
+        let mut lengths = vec![0u8; NC + distance_codes + LDC + RC];
+        let mut i = 0;
+        while i < lengths.len() {
+            if bits.is_exhausted() {
+                return Err(malformed("truncated Huffman table"));
+            }
+            match bit_length_codes.decode(bits) {
+                length @ 0..16 => {
+                    lengths[i] = length as u8;
+                    i += 1;
+                }
+                code @ (16 | 17) => {
+                    let n = if code == 16 {
+                        bits.read(3) + 3
+                    } else {
+                        bits.read(7) + 11
+                    };
+                    if i == 0 {
+                        return Err(malformed("invalid Huffman table"));
+                    }
+                    let end = (i + n).min(lengths.len());
+                    let previous = lengths[i - 1];
+                    lengths[i..end].fill(previous);
+                    i = end;
+                }
+                code => {
+                    let n = if code == 18 {
+                        bits.read(3) + 3
+                    } else {
+                        bits.read(7) + 11
+                    };
+                    i = (i + n).min(lengths.len());
+                }
+            }
+        }
+
+        let (main, rest) = lengths.split_at(NC);
+        let (distance, rest) = rest.split_at(distance_codes);
+        let (low_distance, repeat_length) = rest.split_at(LDC);
+        Ok(Self {
+            main: Huffman::new(main),
+            distance: Huffman::new(distance),
+            low_distance: Huffman::new(low_distance),
+            repeat_length: Huffman::new(repeat_length),
+        })
+    }
+}
+
+struct Filter {
+    kind: u8,
+    // Position in the window
+    start: usize,
+    len: usize,
+    channels: usize,
+}
+
+// Filters are applied to data once decompressed, and given the offset of the data in
+// its file; the window keeps the unfiltered data, as later matches refer to it
+fn apply_filter(filter: &Filter, data: &mut [u8], file_offset: usize) {
+    match filter.kind {
+        // Bytes of each channel are grouped together, and stored as differences
+        FILTER_DELTA => {
+            let source = data.to_vec();
+            let mut source = source.iter();
+            for channel in 0..filter.channels {
+                let mut previous = 0u8;
+                for byte in data.iter_mut().skip(channel).step_by(filter.channels) {
+                    previous = previous.wrapping_sub(*source.next().unwrap());
+                    *byte = previous;
+                }
+            }
+        }
+        // x86 CALL (and JMP) instructions, whose relative addresses were made absolute
+        FILTER_E8 | FILTER_E8E9 => {
+            let file_offset = file_offset as u32;
+            let mut pos = 0;
+            while pos + 4 < data.len() {
+                let byte = data[pos];
+                pos += 1;
+                if byte == 0xe8 || (filter.kind == FILTER_E8E9 && byte == 0xe9) {
+                    let offset = (pos as u32).wrapping_add(file_offset) % E8_FILE_SIZE;
+                    let address = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+                    if address & 0x8000_0000 != 0 {
+                        if address.wrapping_add(offset) & 0x8000_0000 == 0 {
+                            data[pos..pos + 4]
+                                .copy_from_slice(&address.wrapping_add(E8_FILE_SIZE).to_le_bytes());
+                        }
+                    } else if address.wrapping_sub(E8_FILE_SIZE) & 0x8000_0000 != 0 {
+                        data[pos..pos + 4]
+                            .copy_from_slice(&address.wrapping_sub(offset).to_le_bytes());
+                    }
+                    pos += 4;
+                }
+            }
+        }
+        // ARM BL instructions, likewise
+        FILTER_ARM => {
+            let mut pos = 0;
+            while pos + 3 < data.len() {
+                if data[pos + 3] == 0xeb {
+                    let offset = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], 0])
+                        .wrapping_sub(((file_offset + pos) / 4) as u32);
+                    data[pos..pos + 3].copy_from_slice(&offset.to_le_bytes()[..3]);
+                }
+                pos += 4;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn read_filter_value(bits: &mut BitReader) -> usize {
+    let n = bits.read(2) + 1;
+    (0..n).map(|i| bits.read(8) << (i * 8)).sum()
+}
+
+fn slot_to_length(bits: &mut BitReader, slot: usize) -> usize {
+    if slot < 8 {
+        return 2 + slot;
+    }
+    let length_bits = slot / 4 - 1;
+    2 + ((4 | (slot & 3)) << length_bits) + bits.read(length_bits)
+}
+
+// The decompressor's state, which is kept between files of a solid archive
+struct Unpacker {
+    // Unfiltered output, of which at least the dictionary size is kept
+    window: Vec<u8>,
+    dictionary_size: usize,
+    old_distances: [usize; 4],
+    last_length: usize,
+    tables: Option<Tables>,
+    filters: Vec<Filter>,
+}
+
+impl Unpacker {
+    fn new() -> Self {
+        Self {
+            window: Vec::new(),
+            dictionary_size: 0,
+            old_distances: [usize::MAX; 4],
+            last_length: 0,
+            tables: None,
+            filters: Vec::new(),
+        }
+    }
+
+    // Matches reaching before the start of the data (or beyond the dictionary) are
+    // invalid, and give zeros
+    fn copy(&mut self, length: usize, distance: usize) {
+        let len = self.window.len();
+        if distance > len || distance > self.dictionary_size {
+            self.window.resize(len + length, 0);
+        } else if distance >= length {
+            self.window
+                .extend_from_within(len - distance..len - distance + length);
+        } else {
+            for i in 0..length {
+                self.window.push(self.window[len - distance + i]);
+            }
+        }
+    }
+
+    fn insert_old_distance(&mut self, distance: usize) {
+        self.old_distances.copy_within(0..3, 1);
+        self.old_distances[0] = distance;
+    }
+
+    fn read_block_header(bits: &mut BitReader) -> Result<(usize, bool, bool), Error> {
+        bits.align();
+        let flags = bits.read(8) as u8;
+        let checksum = bits.read(8) as u8;
+        let size_bytes = usize::from((flags >> 3) & 3) + 1;
+        if size_bytes == 4 {
+            return Err(malformed("invalid compressed block header"));
+        }
+        let size = (0..size_bytes)
+            .map(|i| bits.read(8) << (i * 8))
+            .sum::<usize>();
+        if checksum != 0x5a ^ flags ^ size as u8 ^ (size >> 8) as u8 ^ (size >> 16) as u8 {
+            return Err(malformed("compressed block header checksum mismatch"));
+        }
+
+        // Blocks end part way through their last byte
+        let bit_size = usize::from(flags & 7) + 1;
+        let end = (bits.pos / 8 + size - 1) * 8 + bit_size;
+        Ok((end, flags & 0x40 != 0, flags & 0x80 != 0))
+    }
+
+    fn decompress(&mut self, packed: &[u8], header: &FileHeader) -> Result<Vec<u8>, Error> {
+        let version = header.compression & FCI_VERSION_MASK;
+        if version > 1 {
+            return Err(Error::Unsupported(format!(
+                "compression version ({version})"
+            )));
+        }
+        let extra_distances = version == 1 && header.compression & FCI_RAR5_COMPAT == 0;
+        let shift = (header.compression >> 10) & if version == 0 { 0xf } else { 0x1f };
+        if shift > MAX_DICTIONARY_SHIFT {
+            return Err(Error::Unsupported(String::from("dictionary size")));
+        }
+        let mut dictionary_size = MIN_DICTIONARY_SIZE << shift;
+        if version == 1 {
+            dictionary_size += dictionary_size / 32 * ((header.compression >> 15) & 0x1f);
+        }
+
+        if !header.is_solid() {
+            *self = Self::new();
+        }
+        self.dictionary_size = dictionary_size as usize;
+        self.filters.clear();
+
+        let start = self.window.len();
+        let limit = header
+            .unpacked_size
+            .map(|size| start.saturating_add(size as usize));
+        let mut tables = self.tables.take();
+        let mut bits = BitReader::new(packed);
+        let mut block_end = 0;
+        let mut last_block = false;
+
+        loop {
+            while bits.pos >= block_end {
+                if last_block {
+                    break;
+                }
+                if bits.is_exhausted() {
+                    return Err(malformed("truncated compressed data"));
+                }
+                let table_present;
+                (block_end, last_block, table_present) = Self::read_block_header(&mut bits)?;
+                if table_present {
+                    tables = Some(Tables::read(&mut bits, extra_distances)?);
+                }
+            }
+            if (last_block && bits.pos >= block_end)
+                || limit.is_some_and(|limit| self.window.len() >= limit)
+            {
+                break;
+            }
+            if bits.is_exhausted() {
+                return Err(malformed("truncated compressed data"));
+            }
+            let tables = tables
+                .as_ref()
+                .ok_or_else(|| malformed("missing Huffman tables"))?;
+
+            match tables.main.decode(&mut bits) {
+                literal @ 0..256 => self.window.push(literal as u8),
+                256 => {
+                    let start = self.window.len() + read_filter_value(&mut bits);
+                    let mut len = read_filter_value(&mut bits);
+                    if len > MAX_FILTER_BLOCK_SIZE {
+                        len = 0;
+                    }
+                    let kind = bits.read(3) as u8;
+                    let channels = if kind == FILTER_DELTA {
+                        bits.read(5) + 1
+                    } else {
+                        0
+                    };
+                    self.filters.push(Filter {
+                        kind,
+                        start,
+                        len,
+                        channels,
+                    });
+                }
+                257 => {
+                    if self.last_length != 0 {
+                        self.copy(self.last_length, self.old_distances[0]);
+                    }
+                }
+                slot @ 258..262 => {
+                    let n = slot - 258;
+                    let distance = self.old_distances[n];
+                    self.old_distances.copy_within(0..n, 1);
+                    self.old_distances[0] = distance;
+
+                    let length_slot = tables.repeat_length.decode(&mut bits);
+                    let length = slot_to_length(&mut bits, length_slot);
+                    self.last_length = length;
+                    self.copy(length, distance);
+                }
+                slot => {
+                    let mut length = slot_to_length(&mut bits, slot - 262);
+
+                    let distance_slot = tables.distance.decode(&mut bits);
+                    let mut distance = 1;
+                    let distance_bits = if distance_slot < 4 {
+                        distance += distance_slot;
+                        0
+                    } else {
+                        let distance_bits = distance_slot / 2 - 1;
+                        distance += (2 | (distance_slot & 1)) << distance_bits;
+                        distance_bits
+                    };
+                    if distance_bits >= 4 {
+                        distance += bits.read(distance_bits - 4) << 4;
+                        distance += tables.low_distance.decode(&mut bits);
+                    } else {
+                        distance += bits.read(distance_bits);
+                    }
+
+                    // Short matches are not used for long distances
+                    if distance > 0x100 {
+                        length += 1;
+                        if distance > 0x2000 {
+                            length += 1;
+                            if distance > 0x40000 {
+                                length += 1;
+                            }
+                        }
+                    }
+
+                    self.insert_old_distance(distance);
+                    self.last_length = length;
+                    self.copy(length, distance);
+                }
+            }
+        }
+        self.tables = tables;
+
+        let end = limit.map_or(self.window.len(), |limit| limit.min(self.window.len()));
+        self.window.truncate(end);
+        let mut data = self.window[start..].to_vec();
+        for filter in self.filters.drain(..) {
+            if filter.start >= start && filter.start + filter.len <= end {
+                let offset = filter.start - start;
+                apply_filter(&filter, &mut data[offset..offset + filter.len], offset);
+            }
+        }
+
+        // Only the dictionary is needed for the next file
+        if self.window.len() > self.dictionary_size {
+            self.window
+                .drain(..self.window.len() - self.dictionary_size);
+        }
+
+        Ok(data)
+    }
+}
+
+struct Reader<'a> {
+    get_password: &'a mut dyn FnMut() -> Option<String>,
+    password: Option<String>,
+    // Derived keys are cached, as archives usually use the same salt for each file
+    keys: Vec<(Kdf, Keys)>,
+    unpacker: Unpacker,
+    files: Vec<ArchiveEntry>,
+}
+
+impl Reader<'_> {
+    fn keys(&mut self, kdf: &Kdf) -> Result<Keys, Error> {
+        if let Some((_, keys)) = self.keys.iter().find(|(k, _)| k == kdf) {
+            return Ok(*keys);
+        }
+        if self.password.is_none() {
+            self.password = Some((self.get_password)().ok_or(Error::MissingPassword)?);
+        }
+        let keys = derive_keys(self.password.as_deref().unwrap(), kdf)?;
+        self.keys.push((*kdf, keys));
+        Ok(keys)
+    }
+
+    fn read_file(&mut self, file: FileHeader, mut packed: Vec<u8>) -> Result<(), Error> {
+        let path = PathBuf::from(&file.name);
+        if file.is_dir {
+            self.files.push(ArchiveEntry {
+                path: Some(path),
+                data: EntryData::Directory(Vec::new()),
+            });
+            return Ok(());
+        }
+
+        // Hard links and file copies refer to a file earlier in the archive
+        if let Some((kind, target)) = &file.redirection {
+            let data = match *kind {
+                REDIR_UNIX_SYMLINK | REDIR_WINDOWS_SYMLINK | REDIR_JUNCTION => {
+                    Some(EntryData::Symlink(PathBuf::from(target)))
+                }
+                REDIR_HARD_LINK | REDIR_FILE_COPY => Some(
+                    self.files
+                        .iter()
+                        .rev()
+                        .find(|entry| entry.path.as_deref() == Some(target.as_ref()))
+                        .and_then(|entry| match &entry.data {
+                            EntryData::File(file) => Some(EntryData::File(file.clone())),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| EntryData::Symlink(PathBuf::from(target))),
+                ),
+                _ => None,
+            };
+            if let Some(data) = data {
+                self.files.push(ArchiveEntry {
+                    path: Some(path),
+                    data,
+                });
+                return Ok(());
+            }
+        }
+
+        let keys = match &file.encryption {
+            Some(encryption) => {
+                let keys = self.keys(&encryption.kdf)?;
+                packed.truncate(packed.len() / AES_BLOCK_SIZE * AES_BLOCK_SIZE);
+                decrypt(&keys.key, &encryption.iv, &mut packed);
+                Some(keys)
+            }
+            None => None,
+        };
+
+        // Without a password check value, an incorrect password is only evident from
+        // the data being nonsense
+        let unverified_password = file
+            .encryption
+            .as_ref()
+            .is_some_and(|encryption| encryption.kdf.password_check.is_none());
+
+        let data = if file.method() == 0 {
+            if let Some(size) = file.unpacked_size {
+                packed.truncate(size as usize);
+            }
+            packed
+        } else {
+            match self.unpacker.decompress(&packed, &file) {
+                Err(Error::Malformed(_)) if unverified_password => {
+                    return Err(Error::IncorrectPassword);
+                }
+                result => result?,
+            }
+        };
+
+        if let Some(expected) = file.crc {
+            let mut crc = crc32fast::hash(&data);
+            if let (Some(keys), Some(encryption)) = (keys, &file.encryption)
+                && encryption.use_mac
+            {
+                crc = crc32_to_mac(crc, &keys.hash_key);
+            }
+            if crc != expected {
+                return Err(if unverified_password {
+                    Error::IncorrectPassword
+                } else {
+                    Error::Malformed(format!("checksum mismatch: {}", file.name))
+                });
+            }
+        }
+
+        self.files.push(ArchiveEntry {
+            path: Some(path),
+            data: EntryData::File(FileData::from_bytes(data)),
+        });
+        Ok(())
+    }
+
+    fn read_volumes(&mut self, volumes: &[&[u8]]) -> Result<(), Error> {
+        // The packed data of a file split between volumes
+        let mut split: Option<Vec<u8>> = None;
+
+        for (n, volume) in volumes.iter().enumerate() {
+            if !volume.starts_with(RAR5_MAGIC) {
+                return Err(malformed("volume is not a RAR 5.0 archive"));
+            }
+            let mut pos = RAR5_MAGIC.len();
+            let mut header_key = None;
+
+            let has_next_volume = loop {
+                // Archives should finish with an end of archive header, but may be
+                // truncated after a file
+                if pos >= volume.len() {
+                    break false;
+                }
+                let (block, next) = read_block(volume, pos, header_key.as_ref())?;
+                pos = next;
+
+                match block.kind {
+                    HEAD_MAIN if n == 0 && block.fields().vint()? & MHFL_VOLUME_NUMBER != 0 => {
+                        return Err(malformed("not the first volume"));
+                    }
+                    HEAD_CRYPT => {
+                        let mut fields = block.fields();
+                        let version = fields.vint()?;
+                        if version != CRYPT_VERSION {
+                            return Err(Error::Unsupported(format!(
+                                "encryption version ({version})"
+                            )));
+                        }
+                        let flags = fields.vint()?;
+                        let mut kdf = Kdf::read(&mut fields)?;
+                        if flags & CHFL_PASSWORD_CHECK != 0 {
+                            kdf.read_password_check(&mut fields)?;
+                        }
+                        header_key = Some(self.keys(&kdf)?.key);
+                    }
+                    HEAD_FILE => {
+                        let file = FileHeader::read(&block)?;
+                        let data = &volume[block.data.clone()];
+                        let packed = match split.take() {
+                            Some(mut packed) if block.flags & HFL_SPLIT_BEFORE != 0 => {
+                                packed.extend_from_slice(data);
+                                packed
+                            }
+                            _ if block.flags & HFL_SPLIT_BEFORE != 0 => {
+                                return Err(malformed("file continues from a missing volume"));
+                            }
+                            _ => data.to_vec(),
+                        };
+                        if block.flags & HFL_SPLIT_AFTER != 0 {
+                            split = Some(packed);
+                        } else {
+                            self.read_file(file, packed)?;
+                        }
+                    }
+                    HEAD_END => break block.fields().vint()? & EHFL_NEXT_VOLUME != 0,
+                    // Service headers hold archive comments, NTFS streams, and such
+                    _ => {}
+                }
+            };
+
+            if !has_next_volume {
+                return Ok(());
+            }
+        }
+
+        Err(Error::MissingVolume)
+    }
+}
+
+// Returns a vector of archive entries pertaining to each file, given each volume of the
+// archive in order (or just the one, for most archives).  A password is asked for only
+// once needed, and the archive is not read if none is given
+pub fn get_files_from_rar5_volumes(
+    volumes: &[&[u8]],
+    get_password: &mut dyn FnMut() -> Option<String>,
+) -> Result<Vec<ArchiveEntry>, Error> {
+    let mut reader = Reader {
+        get_password,
+        password: None,
+        keys: Vec::new(),
+        unpacker: Unpacker::new(),
+        files: Vec::new(),
+    };
+    reader.read_volumes(volumes)?;
+    Ok(reader.files)
+}
@@ -0,0 +1,434 @@
+//! Handle RPM package format
+//!
+//! Read packaged files from RPM packages.  An RPM package consists of a lead, a signature header, a main header, and a compressed cpio payload (see [`cpio`](super::cpio)).  The main header records a digest of every packaged file, which we compare against the digests of the payload's contents, warning about any mismatch as it may be evidence of tampering.  NOTE: RPM does not support encryption
+//!
+//! Format reference:
+//!   <rpm-software-management.github.io/rpm/manual/format_v4.html>
+
+use super::{ArchiveHandler, cpio};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, EntryData, Origin},
+    hash,
+};
+use flate2::read::MultiGzDecoder;
+use lzma_rust2::{LzmaReader, XzReader};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Component, Path, PathBuf},
+};
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+const LEAD_LEN: usize = 96;
+const HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+const HEADER_INTRO_LEN: usize = 16;
+const INDEX_ENTRY_LEN: usize = 16;
+
+// Tag data types
+const TYPE_INT16: u32 = 3;
+const TYPE_INT32: u32 = 4;
+const TYPE_INT64: u32 = 5;
+const TYPE_STRING: u32 = 6;
+const TYPE_STRING_ARRAY: u32 = 8;
+const TYPE_I18NSTRING: u32 = 9;
+
+// Header tags
+const TAG_OLDFILENAMES: u32 = 1027;
+const TAG_FILESIZES: u32 = 1028;
+const TAG_FILEMODES: u32 = 1030;
+const TAG_FILEDIGESTS: u32 = 1035;
+const TAG_FILEFLAGS: u32 = 1037;
+const TAG_FILEDEVICES: u32 = 1095;
+const TAG_FILEINODES: u32 = 1096;
+const TAG_DIRINDEXES: u32 = 1116;
+const TAG_BASENAMES: u32 = 1117;
+const TAG_DIRNAMES: u32 = 1118;
+const TAG_PAYLOADCOMPRESSOR: u32 = 1125;
+const TAG_LONGFILESIZES: u32 = 5008;
+const TAG_FILEDIGESTALGO: u32 = 5011;
+const TAG_PAYLOADDIGEST: u32 = 5092;
+const TAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+// Ghost files are listed in the header but not included in the payload
+const FILE_FLAG_GHOST: u32 = 1 << 6;
+
+// Digest algorithms, as numbered by OpenPGP (RFC 4880, section 9.4).  MD5 is the
+// default if the package does not say otherwise
+const DIGEST_ALGO_MD5: u32 = 1;
+const DIGEST_ALGO_SHA1: u32 = 2;
+const DIGEST_ALGO_SHA256: u32 = 8;
+const DIGEST_ALGO_SHA384: u32 = 9;
+const DIGEST_ALGO_SHA512: u32 = 10;
+const DIGEST_ALGO_SHA224: u32 = 11;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+struct IndexEntry {
+    tag: u32,
+    kind: u32,
+    offset: usize,
+    count: usize,
+}
+
+struct Header<'a> {
+    index: Vec<IndexEntry>,
+    store: &'a [u8],
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<u32, String> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| String::from("truncated header"))
+}
+
+impl<'a> Header<'a> {
+    // Parse a header structure starting at `pos`, returning it and the offset of the
+    // byte following it
+    fn parse(buf: &'a [u8], pos: usize) -> Result<(Self, usize), String> {
+        if buf.get(pos..pos + HEADER_MAGIC.len()) != Some(&HEADER_MAGIC) {
+            return Err(format!("missing header magic at offset {pos}"));
+        }
+
+        let n_index = read_u32(buf, pos + 8)? as usize;
+        let store_len = read_u32(buf, pos + 12)? as usize;
+
+        let index_start = pos + HEADER_INTRO_LEN;
+        let store_start = index_start + n_index * INDEX_ENTRY_LEN;
+        let store_end = store_start + store_len;
+        let store = buf
+            .get(store_start..store_end)
+            .ok_or_else(|| String::from("truncated header data"))?;
+
+        let index = (0..n_index)
+            .map(|i| {
+                let entry = index_start + i * INDEX_ENTRY_LEN;
+                Ok(IndexEntry {
+                    tag: read_u32(buf, entry)?,
+                    kind: read_u32(buf, entry + 4)?,
+                    offset: read_u32(buf, entry + 8)? as usize,
+                    count: read_u32(buf, entry + 12)? as usize,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok((Self { index, store }, store_end))
+    }
+
+    fn find(&self, tag: u32) -> Option<&IndexEntry> {
+        self.index.iter().find(|e| e.tag == tag)
+    }
+
+    fn strings(&self, tag: u32) -> Vec<String> {
+        let Some(entry) = self.find(tag) else {
+            return Vec::new();
+        };
+        if !matches!(
+            entry.kind,
+            TYPE_STRING | TYPE_STRING_ARRAY | TYPE_I18NSTRING
+        ) {
+            return Vec::new();
+        }
+
+        self.store
+            .get(entry.offset..)
+            .unwrap_or_default()
+            .split(|&b| b == 0)
+            .take(entry.count)
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+            .collect()
+    }
+
+    fn string(&self, tag: u32) -> Option<String> {
+        self.strings(tag).into_iter().next()
+    }
+
+    fn int32s(&self, tag: u32) -> Vec<u32> {
+        let Some(entry) = self.find(tag).filter(|e| e.kind == TYPE_INT32) else {
+            return Vec::new();
+        };
+
+        (0..entry.count)
+            .map_while(|i| read_u32(self.store, entry.offset + i * 4).ok())
+            .collect()
+    }
+
+    fn int32(&self, tag: u32) -> Option<u32> {
+        self.int32s(tag).into_iter().next()
+    }
+
+    // Integers of any width
+    fn numbers(&self, tag: u32) -> Vec<u64> {
+        let Some(entry) = self.find(tag) else {
+            return Vec::new();
+        };
+        let width = match entry.kind {
+            TYPE_INT16 => 2,
+            TYPE_INT32 => 4,
+            TYPE_INT64 => 8,
+            _ => return Vec::new(),
+        };
+
+        (0..entry.count)
+            .map_while(|i| {
+                let start = entry.offset + i * width;
+                let bytes = self.store.get(start..start + width)?;
+                Some(bytes.iter().fold(0, |n, &b| (n << 8) | u64::from(b)))
+            })
+            .collect()
+    }
+
+    // Full paths of the packaged files, in header order.  Modern packages store each
+    // path as a directory index and a base name; older ones store full paths
+    fn file_names(&self) -> Vec<String> {
+        let base_names = self.strings(TAG_BASENAMES);
+        if base_names.is_empty() {
+            return self.strings(TAG_OLDFILENAMES);
+        }
+
+        let dir_names = self.strings(TAG_DIRNAMES);
+        let dir_indexes = self.int32s(TAG_DIRINDEXES);
+        base_names
+            .iter()
+            .zip(dir_indexes)
+            .map(|(base, i)| {
+                let dir = dir_names.get(i as usize).map(String::as_str).unwrap_or("");
+                format!("{dir}{base}")
+            })
+            .collect()
+    }
+}
+
+// The metadata of every packaged file, for payloads whose cpio headers are stripped down to
+// an index into the header (see [`cpio`](super::cpio))
+//
+// Hard links are listed separately in the header, but (as in the newc format) only the
+// last of each set has its content in the payload
+fn stripped_files(header: &Header) -> Vec<cpio::StrippedFile> {
+    let mut sizes = header.numbers(TAG_LONGFILESIZES);
+    if sizes.is_empty() {
+        sizes = header.numbers(TAG_FILESIZES);
+    }
+    let modes = header.numbers(TAG_FILEMODES);
+    let devices = header.numbers(TAG_FILEDEVICES);
+    let inodes = header.numbers(TAG_FILEINODES);
+
+    let mut links: HashMap<(u64, u64), Vec<usize>> = HashMap::new();
+    for (i, key) in devices
+        .iter()
+        .copied()
+        .zip(inodes.iter().copied())
+        .enumerate()
+    {
+        links.entry(key).or_default().push(i);
+    }
+
+    header
+        .file_names()
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let dev = devices.get(i).copied().unwrap_or_default();
+            let ino = inodes.get(i).copied().unwrap_or_default();
+            let link_set = links.get(&(dev, ino)).map(Vec::as_slice).unwrap_or(&[]);
+            let has_content = link_set.last().is_none_or(|&last| last == i);
+            cpio::StrippedFile {
+                name,
+                dev,
+                ino,
+                mode: modes.get(i).copied().unwrap_or_default() as u32,
+                nlink: link_set.len().max(1) as u64,
+                size: if has_content {
+                    sizes.get(i).copied().unwrap_or_default() as usize
+                } else {
+                    0
+                },
+            }
+        })
+        .collect()
+}
+
+fn compute_digest(algo: u32, data: &[u8]) -> Option<String> {
+    match algo {
+        DIGEST_ALGO_MD5 => Some(hash::compute_hash::<Md5>(data)),
+        DIGEST_ALGO_SHA1 => Some(hash::compute_hash::<Sha1>(data)),
+        DIGEST_ALGO_SHA256 => Some(hash::compute_hash::<Sha256>(data)),
+        DIGEST_ALGO_SHA384 => Some(hash::compute_hash::<Sha384>(data)),
+        DIGEST_ALGO_SHA512 => Some(hash::compute_hash::<Sha512>(data)),
+        DIGEST_ALGO_SHA224 => Some(hash::compute_hash::<Sha224>(data)),
+        _ => None,
+    }
+}
+
+// Payloads are compressed with whichever compressor the package was built with.  We
+// identify it by its magic bytes where possible, as the payload compressor tag is not
+// always present in older packages
+fn decompress_payload(payload: &[u8], compressor: Option<&str>) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let result = if payload.starts_with(GZIP_MAGIC) {
+        MultiGzDecoder::new(payload).read_to_end(&mut bytes)
+    } else if payload.starts_with(XZ_MAGIC) {
+        XzReader::new(payload, true).read_to_end(&mut bytes)
+    } else if payload.starts_with(ZSTD_MAGIC) {
+        ZstdDecoder::new(payload).and_then(|mut zd| zd.read_to_end(&mut bytes))
+    } else if compressor == Some("lzma") {
+        LzmaReader::new_mem_limit(payload, u32::MAX, None)
+            .and_then(|mut lr| lr.read_to_end(&mut bytes))
+    } else if cpio::is_ascii_cpio(payload) {
+        return Ok(payload.to_vec());
+    } else {
+        return Err(format!(
+            "unsupported payload compressor \"{}\"",
+            compressor.unwrap_or("unknown")
+        ));
+    };
+
+    result.map(|_| bytes).map_err(|e| e.to_string())
+}
+
+// Paths in the header are absolute while those in the payload are relative to the
+// root ("./usr/bin"), so we compare only their normal components
+fn normalise_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+// Compare the digest of every file in the payload against the digest recorded for it
+// in the header.  Digests are computed from the (decompressed) payload, as files too large
+// to keep in memory were only hashed with our own algorithm as they were read
+fn verify_file_digests(
+    header: &Header,
+    files: &[ArchiveEntry],
+    contents: &HashMap<PathBuf, &[u8]>,
+    context: &Path,
+) {
+    let algo = header.int32(TAG_FILEDIGESTALGO).unwrap_or(DIGEST_ALGO_MD5);
+    let flags = header.int32s(TAG_FILEFLAGS);
+
+    // Directories, symlinks, and the like have empty digests
+    let mut expected: HashMap<PathBuf, (String, bool)> = header
+        .file_names()
+        .iter()
+        .zip(header.strings(TAG_FILEDIGESTS))
+        .enumerate()
+        .filter(|(_, (_, digest))| !digest.is_empty())
+        .map(|(i, (name, digest))| {
+            let is_ghost = flags.get(i).is_some_and(|f| f & FILE_FLAG_GHOST != 0);
+            (normalise_path(Path::new(name)), (digest, is_ghost))
+        })
+        .collect();
+
+    for file in files {
+        let (Some(path), EntryData::File(_)) = (&file.path, &file.data) else {
+            continue;
+        };
+        let Some(bytes) = contents.get(path) else {
+            continue;
+        };
+        let path = normalise_path(path);
+
+        let Some((digest, _)) = expected.remove(&path) else {
+            eprintln!(
+                "[WARN] File not listed in RPM header: {}",
+                context.join(&path).display()
+            );
+            continue;
+        };
+
+        match compute_digest(algo, bytes) {
+            Some(computed) if computed != digest.to_lowercase() => eprintln!(
+                "[WARN] Digest mismatch (possible tampering): {} (expected {}, computed {})",
+                context.join(&path).display(),
+                digest,
+                computed
+            ),
+            Some(_) => {}
+            None => {
+                eprintln!(
+                    "[WARN] Unsupported RPM file digest algorithm ({algo}); skipping verification"
+                );
+                return;
+            }
+        }
+    }
+
+    for (path, (_, is_ghost)) in expected {
+        if !is_ghost {
+            eprintln!(
+                "[WARN] File listed in RPM header missing from payload: {}",
+                context.join(path).display()
+            );
+        }
+    }
+}
+
+// Compare the digest of the compressed payload against the digest recorded in the
+// header, if the package has one
+fn verify_payload_digest(header: &Header, payload: &[u8], context: &Path) {
+    let Some(digest) = header.string(TAG_PAYLOADDIGEST) else {
+        return;
+    };
+    let algo = header
+        .int32(TAG_PAYLOADDIGESTALGO)
+        .unwrap_or(DIGEST_ALGO_SHA256);
+
+    if let Some(computed) = compute_digest(algo, payload)
+        && computed != digest.to_lowercase()
+    {
+        eprintln!(
+            "[WARN] Payload digest mismatch (possible tampering): {} (expected {}, computed {})",
+            context.display(),
+            digest,
+            computed
+        );
+    }
+}
+
+fn parse_rpm(buf: &[u8], context: &Path) -> Result<Vec<ArchiveEntry>, String> {
+    if buf.len() < LEAD_LEN {
+        return Err(String::from("truncated lead"));
+    }
+
+    // The signature header is padded to a multiple of eight bytes
+    let (_signature, pos) = Header::parse(buf, LEAD_LEN)?;
+    let (header, pos) = Header::parse(buf, pos.next_multiple_of(8))?;
+
+    let payload = &buf[pos..];
+    verify_payload_digest(&header, payload, context);
+
+    let compressor = header.string(TAG_PAYLOADCOMPRESSOR);
+    let archive = decompress_payload(payload, compressor.as_deref())?;
+    let mut contents = HashMap::new();
+    let files = cpio::parse_cpio(&archive, &stripped_files(&header), |path, data| {
+        contents.insert(path.to_path_buf(), data);
+    })
+    .map_err(|e| format!("cpio payload: {e}"))?;
+    verify_file_digests(&header, &files, &contents, context);
+
+    Ok(files)
+}
+
+pub struct Rpm;
+
+impl ArchiveHandler for Rpm {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Rpm
+    }
+
+    fn name(&self) -> &'static str {
+        "rpm"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-rpm"]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        parse_rpm(bytes, origin.path()).map_or_else(|e| origin.read_error("RPM package", e), Some)
+    }
+}
@@ -2,14 +2,12 @@
 //!
 //! Read archive files from (potentially password-protected) 7zip files
 
-use crate::archive::{ArchiveEntry, EntryData};
-use rpassword::prompt_password;
+use super::ArchiveHandler;
+use crate::archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
 use sevenz_rust2::{ArchiveReader, Error::PasswordRequired, Password};
 use std::{
-    fs::File,
-    io::{Cursor, Read, Seek},
-    path::{Path, PathBuf},
-    process,
+    io::{self, Cursor, Read, Seek},
+    path::PathBuf,
 };
 
 fn sz_archive_is_unencrypted<R>(szr: &mut ArchiveReader<R>) -> bool
@@ -22,29 +20,29 @@ where
     // TODO: is there a better way to check if the file is still encrypted?  E.g.,
     //   https://docs.rs/crate/sevenz-rust/0.6.1/source/src/decoders.rs#142
     szr.for_each_entries(|_file, reader| {
-        let mut content = Vec::new();
-        reader.read_to_end(&mut content)?;
+        io::copy(reader, &mut io::sink())?;
         Ok(true)
     })
     .is_ok()
 }
 
 // Given an opened and decrypted ArchiveReader over any source that implements
-// Read and Seek, walk ever entry and collate its contents into a flat Vec<ArchiveEntry>
-fn get_files_from_7z_reader<R>(mut szr: ArchiveReader<R>) -> Vec<ArchiveEntry>
+// Read and Seek, walk every entry and collate its contents into a flat Vec<ArchiveEntry>
+fn get_files_from_7z_reader<R>(
+    mut szr: ArchiveReader<R>,
+    origin: Origin,
+) -> Option<Vec<ArchiveEntry>>
 where
     R: Read + Seek,
 {
     let mut files = Vec::new();
 
-    szr.for_each_entries(|file, reader| {
+    let result = szr.for_each_entries(|file, reader| {
         let file_name = file.name.clone();
         let data = if file.is_directory() {
             EntryData::Directory(Vec::new())
         } else {
-            let mut bytes = Vec::new();
-            let _ = reader.read_to_end(&mut bytes)?;
-            EntryData::File(bytes)
+            EntryData::File(FileData::from_reader(reader)?)
         };
         files.push(ArchiveEntry {
             path: Some(PathBuf::from(file_name)),
@@ -52,117 +50,68 @@ where
         });
 
         Ok(true)
-    })
-    .unwrap();
-
-    files
-}
-
-fn try_decrypt_from_7z_archive(path: &String) -> ArchiveReader<File> {
-    let password = prompt_password("Enter password: ").unwrap();
-    let password = Password::from(password.as_str());
-
-    let mut szr = match ArchiveReader::open(path, password) {
-        Ok(szr) => szr,
-        Err(_) => {
-            eprintln!("[ERROR] Incorrect password");
-            process::exit(1);
-        }
-    };
+    });
 
-    // Confirm that we have successfully decrypted the archive; that is, all of its
-    // contents are readable
-    if !sz_archive_is_unencrypted(&mut szr) {
-        eprintln!("[ERROR] Incorrect password");
-        process::exit(1);
+    match result {
+        Ok(()) => Some(files),
+        Err(e) => origin.read_error("7z archive", e),
     }
-
-    szr
 }
 
-// Variant of `try_decrypt_from_7z_archive` on buffer, not path
-//
-// This is used for recursing into nested archives
 fn try_decrypt_from_7z_bytes<'a>(
     bytes: &'a [u8],
-    context: &'a Path,
-) -> ArchiveReader<Cursor<&'a [u8]>> {
-    let password = prompt_password(format!("Enter password ({}): ", context.display())).unwrap();
+    origin: Origin,
+) -> Option<ArchiveReader<Cursor<&'a [u8]>>> {
+    let password = origin.password()?;
     let password = Password::from(password.as_str());
 
-    let mut szr = match ArchiveReader::new(Cursor::new(bytes), password) {
-        Ok(szr) => szr,
-        Err(_) => {
-            eprintln!("[ERROR] Incorrect password");
-            process::exit(1);
-        }
+    let Ok(mut szr) = ArchiveReader::new(Cursor::new(bytes), password) else {
+        return origin.incorrect_password();
     };
 
     // Confirm that we have successfully decrypted the archive; that is, all of its
     // contents are readable
     if !sz_archive_is_unencrypted(&mut szr) {
-        eprintln!("[ERROR] Incorrect password");
-        process::exit(1);
+        return origin.incorrect_password();
     }
 
-    szr
+    Some(szr)
 }
 
-pub fn get_files_from_7z_archive(path: &String) -> Vec<ArchiveEntry> {
-    // 7z files can be fully encrypted, or its file contents are encrypted but not the
-    // header.  If the former, then `ArchiveReader::new` will fail.  Even if we seemingly
-    // open the archive, we need to confirm that all of its contents are readable.
-    //
-    // TODO: try three more times if password did not work?
-    // TODO: Note that MaybeBadPassword could also be due to corrupted 7z files
-    let szr = match ArchiveReader::open(path, Password::from("")) {
-        Ok(mut szr) => {
-            if sz_archive_is_unencrypted(&mut szr) {
-                szr
-            } else {
-                try_decrypt_from_7z_archive(path)
-            }
-        }
-        Err(PasswordRequired) => try_decrypt_from_7z_archive(path),
-        Err(e) => {
-            eprintln!("[ERROR] Failed to open archive: {e}");
-            process::exit(1);
-        }
-    };
+pub struct SevenZip;
 
-    get_files_from_7z_reader(szr)
-}
+impl ArchiveHandler for SevenZip {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::SevenZip
+    }
 
-// Variant of `get_files_from_7z_archive` on buffer, not path
-//
-// This is used for recursing into nested archives
-pub fn get_files_from_7z_bytes(
-    bytes: Vec<u8>,
-    allow_nested_encryption: bool,
-    context: &Path,
-) -> Result<Vec<ArchiveEntry>, Vec<u8>> {
-    let szr = match ArchiveReader::new(Cursor::new(bytes.as_slice()), Password::from("")) {
-        Ok(mut szr) => {
-            if sz_archive_is_unencrypted(&mut szr) {
-                szr
-            } else if allow_nested_encryption {
-                try_decrypt_from_7z_bytes(&bytes, context)
-            } else {
-                return Err(bytes);
-            }
-        }
-        Err(PasswordRequired) => {
-            if allow_nested_encryption {
-                try_decrypt_from_7z_bytes(&bytes, context)
-            } else {
-                return Err(bytes);
+    fn name(&self) -> &'static str {
+        "7zip"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-7z-compressed"]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        // 7z files can be fully encrypted, or its file contents are encrypted but not the
+        // header.  If the former, then `ArchiveReader::new` will fail.  Even if we seemingly
+        // open the archive, we need to confirm that all of its contents are readable.
+        //
+        // TODO: try three more times if password did not work?
+        // TODO: Note that MaybeBadPassword could also be due to corrupted 7z files
+        let szr = match ArchiveReader::new(Cursor::new(bytes), Password::from("")) {
+            Ok(mut szr) => {
+                if sz_archive_is_unencrypted(&mut szr) {
+                    szr
+                } else {
+                    try_decrypt_from_7z_bytes(bytes, origin)?
+                }
             }
-        }
-        Err(e) => {
-            eprintln!("[ERROR] Failed to open nested archive: {e}");
-            process::exit(1);
-        }
-    };
+            Err(PasswordRequired) => try_decrypt_from_7z_bytes(bytes, origin)?,
+            Err(e) => return origin.read_error("7z archive", e),
+        };
 
-    Ok(get_files_from_7z_reader(szr))
+        get_files_from_7z_reader(szr, origin)
+    }
 }
@@ -0,0 +1,261 @@
+//! Handle self-extracting and appended archives
+//!
+//! Find archives embedded in executables (PE and ELF files).  Self-extracting archives are an executable stub with an archive appended, and other executables may carry an archive in their overlay or resources.  We scan for the signatures of zip, 7z, RAR, and cabinet files, and expose each archive found as a nested archive.  NOTE: encryption support depends on that of the embedded archive's format
+//!
+//! Signatures are validated before use (for example, by the zip central directory's location, or the 7z start header's CRC), as short byte sequences are likely to occur by chance in a large executable.
+
+use super::ArchiveHandler;
+use crate::archive::{self, ArchiveEntry, ArchiveType, EntryData, FileData, Origin};
+use memchr::memmem;
+use std::path::PathBuf;
+
+// Zip end of central directory record, and the central directory header that it points to
+//   <pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+const ZIP_EOCD_MAGIC: &[u8] = b"PK\x05\x06";
+const ZIP_EOCD_LEN: usize = 22;
+const ZIP_CENTRAL_DIRECTORY_MAGIC: &[u8] = b"PK\x01\x02";
+
+// 7z signature header, containing the CRC of the start header that follows it
+//   <github.com/ip7z/7zip/blob/24.09/DOC/7zFormat.txt>
+const SEVEN_ZIP_MAGIC: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const SEVEN_ZIP_HEADER_LEN: usize = 32;
+
+// RAR 4 marker block is followed by the main archive header (type 0x73), while RAR 5
+// has a longer signature, followed by its main archive header (type 1), or an archive
+// encryption header (type 4) if its headers are encrypted.  These headers start with a CRC
+//   <www.rarlab.com/technote.htm>
+const RAR4_MAGIC: &[u8] = b"Rar!\x1a\x07\x00";
+const RAR4_MAIN_HEADER_TYPE: u8 = 0x73;
+const RAR5_MAGIC: &[u8] = b"Rar!\x1a\x07\x01\x00";
+const RAR5_FIRST_HEADER_TYPES: [u64; 2] = [1, 4];
+
+// Cabinet header, whose version is always 1.3
+const CAB_MAGIC: &[u8] = b"MSCF";
+const CAB_VERSION: [u8; 2] = [3, 1];
+
+struct Embedded {
+    archive_type: ArchiveType,
+    start: usize,
+    end: usize,
+}
+
+// Validates a signature found at the given offset, returning the extent of its archive
+type Scanner = fn(&[u8], usize) -> Option<Embedded>;
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<usize> {
+    buf.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Option<usize> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> Option<usize> {
+    buf.get(pos..pos + 8)
+        .and_then(|b| usize::try_from(u64::from_le_bytes(b.try_into().ok()?)).ok())
+}
+
+// The zip format is located from its end: the end of central directory record gives
+// the size and offset of the central directory, from which we find where the archive
+// starts.  Self-extracting archives whose offsets have been adjusted to be relative to
+// the start of the executable (as with `zip -A`) start at zero
+fn find_zip(buf: &[u8], eocd: usize) -> Option<Embedded> {
+    let cd_size = read_u32(buf, eocd + 12)?;
+    let cd_offset = read_u32(buf, eocd + 16)?;
+    let comment_len = read_u16(buf, eocd + 20)?;
+
+    let cd_start = eocd.checked_sub(cd_size)?;
+    if cd_size == 0 || !buf[cd_start..].starts_with(ZIP_CENTRAL_DIRECTORY_MAGIC) {
+        return None;
+    }
+
+    Some(Embedded {
+        archive_type: ArchiveType::Zip,
+        start: cd_start.checked_sub(cd_offset)?,
+        end: (eocd + ZIP_EOCD_LEN + comment_len).min(buf.len()),
+    })
+}
+
+fn find_7z(buf: &[u8], start: usize) -> Option<Embedded> {
+    let header = buf.get(start..start + SEVEN_ZIP_HEADER_LEN)?;
+    if crc32fast::hash(&header[12..32]) != read_u32(header, 8)? as u32 {
+        return None;
+    }
+
+    let next_header_offset = read_u64(header, 12)?;
+    let next_header_size = read_u64(header, 20)?;
+    let end = start
+        .checked_add(SEVEN_ZIP_HEADER_LEN)?
+        .checked_add(next_header_offset)?
+        .checked_add(next_header_size)?;
+
+    (end <= buf.len()).then_some(Embedded {
+        archive_type: ArchiveType::SevenZip,
+        start,
+        end,
+    })
+}
+
+// Read a RAR 5 variable-length integer, returning it and its length
+fn read_vint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, &byte) in buf.iter().take(10).enumerate() {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+// The RAR 4 main header's CRC is the low half of the CRC32 of the header after the CRC
+fn is_rar4(buf: &[u8]) -> bool {
+    let header = &buf[RAR4_MAGIC.len()..];
+    let (Some(crc), Some(size)) = (read_u16(header, 0), read_u16(header, 5)) else {
+        return false;
+    };
+    header.get(2) == Some(&RAR4_MAIN_HEADER_TYPE)
+        && header
+            .get(2..size)
+            .is_some_and(|h| crc32fast::hash(h) & 0xffff == crc as u32)
+}
+
+// The RAR 5 first header's CRC32 covers its size and the header itself
+fn is_rar5(buf: &[u8]) -> bool {
+    let header = &buf[RAR5_MAGIC.len()..];
+    let Some(((size, size_len), crc)) =
+        header.get(4..).and_then(read_vint).zip(read_u32(header, 0))
+    else {
+        return false;
+    };
+    let Some(checked) = usize::try_from(size)
+        .ok()
+        .and_then(|size| header.get(4..4 + size_len + size))
+    else {
+        return false;
+    };
+    crc32fast::hash(checked) == crc as u32
+        && read_vint(&checked[size_len..])
+            .is_some_and(|(t, _)| RAR5_FIRST_HEADER_TYPES.contains(&t))
+}
+
+// RAR archives do not record their total size, so we take everything to the end
+fn find_rar(buf: &[u8], start: usize) -> Option<Embedded> {
+    let rest = &buf[start..];
+    let is_rar = if rest.starts_with(RAR5_MAGIC) {
+        is_rar5(rest)
+    } else {
+        is_rar4(rest)
+    };
+
+    is_rar.then_some(Embedded {
+        archive_type: ArchiveType::Rar,
+        start,
+        end: buf.len(),
+    })
+}
+
+fn find_cab(buf: &[u8], start: usize) -> Option<Embedded> {
+    let reserved = (read_u32(buf, start + 4)?, read_u32(buf, start + 12)?);
+    let size = read_u32(buf, start + 8)?;
+    let version = buf.get(start + 24..start + 26)?;
+
+    (reserved == (0, 0) && version == CAB_VERSION && start + size <= buf.len()).then_some(
+        Embedded {
+            archive_type: ArchiveType::Cab,
+            start,
+            end: start + size,
+        },
+    )
+}
+
+// Find each (non-overlapping) archive embedded in the buffer, in order
+fn find_embedded_archives(buf: &[u8]) -> Vec<Embedded> {
+    let scanners: [(&[u8], Scanner); 5] = [
+        (ZIP_EOCD_MAGIC, find_zip),
+        (SEVEN_ZIP_MAGIC, find_7z),
+        (RAR4_MAGIC, find_rar),
+        (RAR5_MAGIC, find_rar),
+        (CAB_MAGIC, find_cab),
+    ];
+
+    let mut candidates = scanners
+        .iter()
+        .flat_map(|(magic, scan)| memmem::find_iter(buf, magic).filter_map(|pos| scan(buf, pos)))
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|embedded| (embedded.start, std::cmp::Reverse(embedded.end)));
+
+    // Any archive found within another (such as a zip stored in a 7z archive) will be
+    // found again when we recurse into the outer archive
+    let mut embedded: Vec<Embedded> = Vec::new();
+    for candidate in candidates {
+        if embedded
+            .last()
+            .is_none_or(|last| candidate.start >= last.end)
+        {
+            embedded.push(candidate);
+        }
+    }
+    embedded
+}
+
+pub struct Sfx;
+
+impl ArchiveHandler for Sfx {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Sfx
+    }
+
+    fn name(&self) -> &'static str {
+        "sfx"
+    }
+
+    // Executables may have an archive embedded (such as self-extracting archives)
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/x-executable",
+            "application/vnd.microsoft.portable-executable",
+        ]
+    }
+
+    // Returns the archives embedded in an executable, each as a nested archive
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let embedded = find_embedded_archives(bytes);
+        // Most executables do not have an archive embedded
+        if embedded.is_empty() {
+            return origin.no_entries("No embedded archive found in executable");
+        }
+
+        let mut files = Vec::with_capacity(embedded.len());
+        for Embedded {
+            archive_type,
+            start,
+            end,
+        } in embedded
+        {
+            let name = format!("({} archive at 0x{start:x})", String::from(archive_type));
+            let entry_context = origin.path().join(&name);
+            let bytes = &bytes[start..end];
+            let nested = archive::get_file_data(
+                bytes,
+                archive_type,
+                Origin::Nested {
+                    context: &entry_context,
+                    allow_encryption: origin.allows_encryption(),
+                },
+            );
+            let data = match nested {
+                Some(nested) => EntryData::NestedArchive(nested.entries),
+                None => EntryData::File(FileData::from_slice(bytes)),
+            };
+            files.push(ArchiveEntry {
+                path: Some(PathBuf::from(name)),
+                data,
+            });
+        }
+
+        Some(files)
+    }
+}
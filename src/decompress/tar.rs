@@ -2,52 +2,138 @@
 //!
 //! Read archive files from tar files.  .tar.gz files are handled separately in [`gzip`](super::gzip).  NOTE: tar does not support encryption
 
-use crate::archive::{ArchiveEntry, EntryData};
+use super::{ArchiveHandler, oci, package};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin},
+    config,
+};
 use std::{
-    fs::File,
-    io::{Cursor, Read},
+    io::{self, Read},
+    path::PathBuf,
 };
-use tar::Archive;
+use tar::{Archive, Header};
 
 pub trait ReadTarArchive {
-    fn get_files_from_tar(self) -> Vec<ArchiveEntry>;
+    fn get_files_from_tar(self) -> io::Result<Vec<ArchiveEntry>>;
 }
 
+// Tarballs are read as they are decompressed, so a corrupt compressed stream shows up as
+// an error reading the tar archive
 impl<R: Read> ReadTarArchive for Archive<R> {
-    fn get_files_from_tar(mut self) -> Vec<ArchiveEntry> {
+    fn get_files_from_tar(mut self) -> io::Result<Vec<ArchiveEntry>> {
         let mut files = Vec::new();
 
         // https://rust-lang-nursery.github.io/rust-cookbook/compression/tar.html#decompress-a-tarball-while-removing-a-prefix-from-the-paths
-        self.entries()
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .for_each(|mut entry| {
-                let path_buf = entry.path().ok().map(|p| p.into_owned());
-                let data = if entry.header().entry_type().is_dir() {
-                    EntryData::Directory(Vec::new())
-                } else {
-                    let mut bytes = Vec::new();
-                    entry.read_to_end(&mut bytes).unwrap();
-                    EntryData::File(bytes)
-                };
-                files.push(ArchiveEntry {
-                    path: path_buf,
-                    data,
-                });
+        for entry in self.entries()? {
+            let mut entry = entry?;
+            let path_buf = entry.path().ok().map(|p| p.into_owned());
+            let entry_type = entry.header().entry_type();
+            let data = if entry_type.is_dir() {
+                EntryData::Directory(Vec::new())
+            } else if let (true, Ok(Some(target))) = (entry_type.is_symlink(), entry.link_name()) {
+                EntryData::Symlink(target.into_owned())
+            } else {
+                EntryData::File(FileData::from_reader(&mut entry)?)
+            };
+            files.push(ArchiveEntry {
+                path: path_buf,
+                data,
             });
+        }
+
+        Ok(files)
+    }
+}
+
+pub struct Tar;
+
+impl ArchiveHandler for Tar {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Tar
+    }
+
+    fn name(&self) -> &'static str {
+        "tar"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-tar"]
+    }
 
-        files
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        Archive::new(bytes)
+            .get_files_from_tar()
+            .map_or_else(|e| origin.read_error("tar archive", e), Some)
+    }
+
+    fn container_kind(&self, entries: &[ArchiveEntry]) -> Option<&'static str> {
+        package::package_kind(entries)
+    }
+
+    // Packages which record the digests of their own contents (such as Ruby gems) are
+    // verified against them.  Container images are tarballs of layers, each a tarball
+    // itself, so we list the files of each layer (or of the image as a whole, once squashed)
+    fn post_process(&self, entries: Vec<ArchiveEntry>, origin: Origin) -> Vec<ArchiveEntry> {
+        package::verify_package(&entries, origin.path());
+        oci::get_image_layers(&entries, config::get().squash).unwrap_or(entries)
     }
 }
 
-pub fn get_files_from_tar(path: &String) -> Vec<ArchiveEntry> {
-    let file = File::open(path).unwrap();
-    Archive::new(file).get_files_from_tar()
+// Returns a vector of archive entries pertaining to each file, read from a stream as it is
+// decompressed
+pub fn get_files_from_tar_stream<R: Read>(reader: R) -> io::Result<Vec<ArchiveEntry>> {
+    Archive::new(reader).get_files_from_tar()
 }
 
-// Returns a vector of archive entries pertaining to each file, from buffer
+// A block is a tar header if it has the ustar magic (as POSIX and GNU tar write), or
+// otherwise (as pre-POSIX tar did not write one) if its checksum is valid
 //
-// This is used for recursing into nested archives
-pub fn get_files_from_tar_bytes(bytes: Vec<u8>) -> Vec<ArchiveEntry> {
-    Archive::new(Cursor::new(bytes)).get_files_from_tar()
+// This needs only the first 512 bytes of the stream, so can be checked before the rest
+// is decompressed
+pub fn is_tar_header(block: &[u8]) -> bool {
+    let Some(block) = block.get(..512) else {
+        return false;
+    };
+    if &block[257..262] == b"ustar" {
+        return true;
+    }
+
+    // The checksum is the sum of the header's bytes, taking the checksum field as spaces
+    let sum: u32 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| {
+            if (148..156).contains(&i) {
+                0x20
+            } else {
+                b.into()
+            }
+        })
+        .sum();
+    Header::from_byte_slice(block)
+        .cksum()
+        .is_ok_and(|cksum| cksum == sum)
+}
+
+// Given the decompressed stream of a single-stream compression format (such as
+// Zstandard), return the tarball's entries if it is one, or otherwise the content as
+// a single file named `name`.  Either way, the stream is read only once, as it is
+// decompressed
+//
+// Compression-only formats know nothing about file structure, so the caller has to
+// construct the inner file name itself (usually from the outer file name)
+pub fn get_files_from_tar_or_stream<R: Read>(
+    mut reader: R,
+    name: Option<PathBuf>,
+) -> io::Result<Vec<ArchiveEntry>> {
+    let mut head = Vec::with_capacity(512);
+    reader.by_ref().take(512).read_to_end(&mut head)?;
+    if is_tar_header(&head) {
+        return get_files_from_tar_stream(head.chain(reader));
+    }
+
+    Ok(vec![ArchiveEntry {
+        path: name,
+        data: EntryData::File(FileData::from_reader(head.chain(reader))?),
+    }])
 }
@@ -0,0 +1,52 @@
+//! Handle xz archive format
+//!
+//! Read archive files from xz and tarball (.tar.xz) files.  Like [`gzip`](super::gzip), xz only does compression, so plain and tarball content is handled together.  NOTE: xz does not support encryption
+
+use super::{ArchiveHandler, oci, package, tar};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, Origin},
+    config,
+};
+use lzma_rust2::XzReader;
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+// Decompress every stream of an xz file, reading its content as it is decompressed
+fn get_files_from_xz<R: Read>(reader: R, name: Option<PathBuf>) -> io::Result<Vec<ArchiveEntry>> {
+    tar::get_files_from_tar_or_stream(XzReader::new(reader, true), name)
+}
+
+pub struct Xz;
+
+impl ArchiveHandler for Xz {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Xz
+    }
+
+    fn name(&self) -> &'static str {
+        "xz"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/x-xz"]
+    }
+
+    // The xz format does not store a file name, so we construct the inner file name
+    // from the archive's path
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        get_files_from_xz(bytes, name).map_or_else(|e| origin.read_error("xz stream", e), Some)
+    }
+
+    fn container_kind(&self, entries: &[ArchiveEntry]) -> Option<&'static str> {
+        package::package_kind(entries)
+    }
+
+    // Container images are tarballs of layers, each a tarball itself, so we list the files
+    // of each layer (or of the image as a whole, once squashed)
+    fn post_process(&self, entries: Vec<ArchiveEntry>, _origin: Origin) -> Vec<ArchiveEntry> {
+        oci::get_image_layers(&entries, config::get().squash).unwrap_or(entries)
+    }
+}
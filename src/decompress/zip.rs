@@ -1,20 +1,15 @@
 //! Handle zip archive format
 //!
-//! Read archive files from (potentially password-protected) zip files
-
-use crate::archive::{ArchiveEntry, EntryData};
-use rpassword::prompt_password;
-use std::{
-    fs::File,
-    io::{BufReader, Cursor, Read, Seek},
-    path::Path,
-    process,
-};
-use zip::{
-    ZipArchive,
-    read::ZipFile,
-    result::{ZipError, ZipResult},
+//! Read archive files from (potentially password-protected) zip files.  Entries are compressed independently, so (unless encrypted) are decompressed and hashed in parallel
+
+use super::{ArchiveHandler, package};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, EntryData, FileData, Origin},
+    tree::find_entry,
 };
+use rayon::prelude::*;
+use std::io::{Cursor, Read, Seek};
+use zip::{ZipArchive, read::ZipFile, result::ZipError};
 
 fn zip_archive_is_encrypted<R>(archive: &mut ZipArchive<R>) -> bool
 where
@@ -30,99 +25,199 @@ where
     )
 }
 
-fn try_decrypt_from_zip_archive_index<'a, R>(
+fn get_files_from_zip_archive_index<'a, R>(
     archive: &'a mut ZipArchive<R>,
     i: usize,
-    context: Option<&Path>,
-) -> ZipResult<ZipFile<'a>>
+    origin: Origin,
+) -> Option<ZipFile<'a>>
 where
     R: Seek + Read,
 {
-    let prompt = match context {
-        Some(ctx) => format!("Enter password ({}): ", ctx.display()),
-        None => "Enter password: ".to_string(),
+    let file = if !zip_archive_is_encrypted(archive) {
+        archive.by_index(i)
+    } else {
+        // TODO: try 3 more times before giving up.  I am having issues with mutable lifetimes
+        // of the archive object so I am just trying once for now
+        let password = origin.password()?;
+        archive.by_index_decrypt(i, password.as_bytes())
     };
-    let password = prompt_password(prompt).unwrap();
-    let password = password.as_bytes();
 
-    archive.by_index_decrypt(i, password)
+    match file {
+        Ok(file) => Some(file),
+        Err(ZipError::InvalidPassword) => origin.incorrect_password(),
+        Err(e) => origin.read_error("zip archive", e),
+    }
 }
 
-fn get_files_from_zip_archive_index<'a, R>(
-    archive: &'a mut ZipArchive<R>,
+fn get_file_from_zip_archive_index<R>(
+    archive: &mut ZipArchive<R>,
     i: usize,
-    context: Option<&Path>,
-) -> ZipFile<'a>
+    origin: Origin,
+) -> Option<ArchiveEntry>
 where
-    R: Seek + Read,
+    R: Read + Seek,
 {
-    if !zip_archive_is_encrypted(archive) {
-        archive.by_index(i).unwrap()
-    } else if let Ok(file) = try_decrypt_from_zip_archive_index(archive, i, context) {
-        file
-    } else {
-        // TODO: try 3 more times before giving up.  I am having issues with mutable lifetimes
-        // of the archive object so I am just trying once for now
-        eprintln!("[ERROR] Incorrect password");
-        process::exit(1);
-    }
-}
+    let mut file = get_files_from_zip_archive_index(archive, i, origin)?;
 
-// Read bytes from zip file contained within zip archive
-fn get_bytes_from_zip_file(file: &mut ZipFile) -> Vec<u8> {
-    // TODO: read in parts so that the full file is never in memory
-    let mut content = Vec::new();
-    let _ = file.read_to_end(&mut content);
-    content
+    // Read (and hash) the file in parts, so that the full file need never be in memory
+    let data = if file.is_dir() {
+        EntryData::Directory(Vec::new())
+    } else {
+        match FileData::from_reader(&mut file) {
+            Ok(data) => EntryData::File(data),
+            Err(e) => return origin.read_error("zip archive", e),
+        }
+    };
+    Some(ArchiveEntry {
+        path: file.enclosed_name(),
+        data,
+    })
 }
 
 // Given an opened ZipArchive over any source that implements Read and Seek,
-// walk ever entry and collate its contents into a flat Vec<ArchiveEntry>
+// walk every entry and collate its contents into a flat Vec<ArchiveEntry>
+//
+// Each thread reads entries through its own clone of the archive (sharing its parsed
+// central directory), each with its own position within the buffer
 fn get_files_from_zip_archive_reader<R>(
     mut archive: ZipArchive<R>,
-    context: Option<&Path>,
-) -> Vec<ArchiveEntry>
+    origin: Origin,
+) -> Option<Vec<ArchiveEntry>>
 where
-    R: Read + Seek,
+    R: Read + Seek + Clone + Send + Sync,
 {
-    let mut files = Vec::new();
-    for i in 0..archive.len() {
-        let mut file = get_files_from_zip_archive_index(&mut archive, i, context);
-        let data = if file.is_dir() {
-            EntryData::Directory(Vec::new())
-        } else {
-            EntryData::File(get_bytes_from_zip_file(&mut file))
-        };
-        files.push(ArchiveEntry {
-            path: file.enclosed_name(),
-            data,
-        });
+    // We prompt for the password of an encrypted archive for each entry, so these are read
+    // in order
+    if zip_archive_is_encrypted(&mut archive) {
+        return (0..archive.len())
+            .map(|i| get_file_from_zip_archive_index(&mut archive, i, origin))
+            .collect();
     }
-    files
+
+    (0..archive.len())
+        .into_par_iter()
+        .map_init(
+            || archive.clone(),
+            |archive, i| get_file_from_zip_archive_index(archive, i, origin),
+        )
+        .collect()
 }
 
-// Returns a vector of archive entries pertaining to each file, from path
-pub fn get_files_from_zip_archive(path: &String) -> Vec<ArchiveEntry> {
-    let file = File::open(Path::new(path)).unwrap();
-    let buf = BufReader::new(file);
-    let archive = ZipArchive::new(buf).unwrap();
-    get_files_from_zip_archive_reader(archive, None)
+pub struct Zip;
+
+impl ArchiveHandler for Zip {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Zip
+    }
+
+    fn name(&self) -> &'static str {
+        "zip"
+    }
+
+    // Many document formats are zip archives underneath
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/zip",
+            "application/epub+zip",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "application/vnd.oasis.opendocument.text",
+            "application/vnd.oasis.opendocument.spreadsheet",
+            "application/vnd.oasis.opendocument.presentation",
+        ]
+    }
+
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        match ZipArchive::new(Cursor::new(bytes)) {
+            Ok(archive) => get_files_from_zip_archive_reader(archive, origin),
+            Err(e) => origin.read_error("zip archive", e),
+        }
+    }
+
+    fn container_kind(&self, entries: &[ArchiveEntry]) -> Option<&'static str> {
+        container_kind(entries)
+    }
+
+    // Packages which record the digests of their own contents (such as Python wheels) are
+    // verified against them
+    fn post_process(&self, entries: Vec<ArchiveEntry>, origin: Origin) -> Vec<ArchiveEntry> {
+        package::verify_package(&entries, origin.path());
+        entries
+    }
 }
 
-// Returns a vector of archive entries pertaining to each file, from buffer
-//
-// This is used for recursing into nested archives
-pub fn get_files_from_zip_bytes(
-    bytes: Vec<u8>,
-    allow_nested_encryption: bool,
-    context: &Path,
-) -> Result<Vec<ArchiveEntry>, Vec<u8>> {
-    let cursor = Cursor::new(bytes.as_slice());
-    let mut archive = ZipArchive::new(cursor).unwrap();
+// Many file formats are zip archives with a particular layout (such as Office documents,
+// Java archives, and Android packages).  Identify which, if any, from the marker files
+// each is required to contain
+pub fn container_kind(entries: &[ArchiveEntry]) -> Option<&'static str> {
+    let has = |path: &str| find_entry(entries, path).is_some();
+    let has_top_level = |suffix| entries.iter().any(|entry| entry.name().ends_with(suffix));
 
-    if zip_archive_is_encrypted(&mut archive) && !allow_nested_encryption {
-        return Err(bytes);
+    // OpenDocument and EPUB files start with an uncompressed file naming their MIME type
+    if let Some(EntryData::File(FileData {
+        bytes: Some(mime_type),
+        ..
+    })) = find_entry(entries, "mimetype").map(|e| &e.data)
+    {
+        let kind = match mime_type.trim_ascii() {
+            b"application/epub+zip" => Some("EPUB e-book"),
+            b"application/vnd.oasis.opendocument.text" => Some("OpenDocument text (ODT)"),
+            b"application/vnd.oasis.opendocument.spreadsheet" => {
+                Some("OpenDocument spreadsheet (ODS)")
+            }
+            b"application/vnd.oasis.opendocument.presentation" => {
+                Some("OpenDocument presentation (ODP)")
+            }
+            m if m.starts_with(b"application/vnd.oasis.opendocument.") => Some("OpenDocument file"),
+            _ => None,
+        };
+        if kind.is_some() {
+            return kind;
+        }
+    }
+
+    // NuGet packages are also Open Packaging Conventions files, so must be checked for
+    // before Office documents
+    if has_top_level(".nuspec") {
+        return Some("NuGet package");
+    }
+    if has("[Content_Types].xml") {
+        return Some(if has("word/") {
+            "Word document (OOXML)"
+        } else if has("xl/") {
+            "Excel workbook (OOXML)"
+        } else if has("ppt/") {
+            "PowerPoint presentation (OOXML)"
+        } else {
+            "Open Packaging Conventions file"
+        });
+    }
+
+    // Android packages are also Java archives, so must be checked for before JAR files
+    if has("BundleConfig.pb") {
+        return Some("Android app bundle (AAB)");
+    }
+    if has("AndroidManifest.xml") {
+        return Some("Android package (APK)");
+    }
+    if entries.iter().any(|entry| {
+        entry.name().ends_with(".dist-info") && has(&format!("{}/WHEEL", entry.name()))
+    }) {
+        return Some("Python wheel");
+    }
+    if has("install.rdf") || has("META-INF/mozilla.rsa") {
+        return Some("Firefox extension (XPI)");
+    }
+    if has("META-INF/application.xml") {
+        return Some("Java enterprise archive (EAR)");
+    }
+    if has("WEB-INF/") {
+        return Some("Java web archive (WAR)");
+    }
+    if has("META-INF/MANIFEST.MF") {
+        return Some("Java archive (JAR)");
     }
 
-    Ok(get_files_from_zip_archive_reader(archive, Some(context)))
+    None
 }
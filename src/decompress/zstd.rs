@@ -0,0 +1,53 @@
+//! Handle Zstandard archive format
+//!
+//! Read archive files from Zstandard and tarball (.tar.zst, .pkg.tar.zst) files.  Like [`gzip`](super::gzip), Zstandard only does compression, so plain and tarball content is handled together.  NOTE: Zstandard does not support encryption
+
+use super::{ArchiveHandler, oci, package, tar};
+use crate::{
+    archive::{ArchiveEntry, ArchiveType, Origin},
+    config,
+};
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+use zstd::stream::read::Decoder;
+
+// Decompress every frame of a Zstandard stream, reading its content as it is decompressed
+fn get_files_from_zstd<R: Read>(reader: R, name: Option<PathBuf>) -> io::Result<Vec<ArchiveEntry>> {
+    Decoder::new(reader).and_then(|zd| tar::get_files_from_tar_or_stream(zd, name))
+}
+
+pub struct Zstd;
+
+impl ArchiveHandler for Zstd {
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Zstd
+    }
+
+    fn name(&self) -> &'static str {
+        "zstd"
+    }
+
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/zstd"]
+    }
+
+    // Unlike gzip, there is no file name stored in the Zstandard format, so we
+    // construct the inner file name from the archive's path
+    fn entries(&self, bytes: &[u8], origin: Origin) -> Option<Vec<ArchiveEntry>> {
+        let name = origin.path().file_stem().map(PathBuf::from);
+        get_files_from_zstd(bytes, name)
+            .map_or_else(|e| origin.read_error("Zstandard stream", e), Some)
+    }
+
+    fn container_kind(&self, entries: &[ArchiveEntry]) -> Option<&'static str> {
+        package::package_kind(entries)
+    }
+
+    // Container images are tarballs of layers, each a tarball itself, so we list the files
+    // of each layer (or of the image as a whole, once squashed)
+    fn post_process(&self, entries: Vec<ArchiveEntry>, _origin: Origin) -> Vec<ArchiveEntry> {
+        oci::get_image_layers(&entries, config::get().squash).unwrap_or(entries)
+    }
+}
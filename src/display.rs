@@ -1,33 +1,22 @@
-use crate::{
-    algo::HashAlgo,
-    archive::{ArchiveEntry, EntryData},
-    hash,
-};
+use crate::archive::{ArchiveEntry, EntryData};
 use colored::*;
 use std::path::Path;
 use tabular::{Table, row};
 
-pub fn print_table(entries: &[ArchiveEntry], algo: &HashAlgo, recurse: u8) {
+pub fn print_table(entries: &[ArchiveEntry], recurse: u8) {
     let mut table = Table::new("{:>}  {:<}");
-    add_entries_to_table(&mut table, entries, algo, recurse, Path::new(""));
+    add_entries_to_table(&mut table, entries, recurse, Path::new(""));
     print!("{}", table);
 }
 
-fn add_entries_to_table(
-    table: &mut Table,
-    entries: &[ArchiveEntry],
-    algo: &HashAlgo,
-    recurse: u8,
-    prefix: &Path,
-) {
+fn add_entries_to_table(table: &mut Table, entries: &[ArchiveEntry], recurse: u8, prefix: &Path) {
     for entry in entries {
         let name = prefix.join(entry.name());
         let name_str = name.to_string_lossy().to_string();
 
         match &entry.data {
-            EntryData::File(bytes) => {
-                let hash = hash::get_hash_from_data(bytes, algo);
-                table.add_row(row!(hash, name_str));
+            EntryData::File(file) => {
+                table.add_row(row!(&file.hash, name_str));
             }
             EntryData::Directory(_) | EntryData::NestedArchive(_) if entry.is_empty_directory() => {
                 table.add_row(row!(String::from("<empty directory>").dimmed(), name_str));
@@ -37,27 +26,25 @@ fn add_entries_to_table(
                     table.add_row(row!(String::from("<directory>").dimmed(), name_str));
                 }
                 _ => {
-                    add_entries_to_table(table, children, algo, recurse, &name);
+                    add_entries_to_table(table, children, recurse, &name);
                 }
             },
             EntryData::NestedArchive(children) => {
-                add_entries_to_table(table, children, algo, recurse, &name);
+                add_entries_to_table(table, children, recurse, &name);
+            }
+            EntryData::Symlink(target) => {
+                let name_str = format!("{} -> {}", name_str, target.display());
+                table.add_row(row!(String::from("<symlink>").dimmed(), name_str));
             }
         }
     }
 }
 
-pub fn print_tree(entries: &[ArchiveEntry], algo: &HashAlgo, recurse: u8, label: Option<&str>) {
-    print_tree_inner(entries, algo, recurse, "", label)
+pub fn print_tree(entries: &[ArchiveEntry], recurse: u8, label: Option<&str>) {
+    print_tree_inner(entries, recurse, "", label)
 }
 
-fn print_tree_inner(
-    entries: &[ArchiveEntry],
-    algo: &HashAlgo,
-    recurse: u8,
-    prefix: &str,
-    label: Option<&str>,
-) {
+fn print_tree_inner(entries: &[ArchiveEntry], recurse: u8, prefix: &str, label: Option<&str>) {
     if let Some(label) = label {
         println!("{}", label);
     }
@@ -70,9 +57,8 @@ fn print_tree_inner(
         let name = entry.name();
 
         match &entry.data {
-            EntryData::File(bytes) => {
-                let hash = hash::get_hash_from_data(bytes, algo);
-                println!("{}{}{}  {}", prefix, connector, hash, name);
+            EntryData::File(file) => {
+                println!("{}{}{}  {}", prefix, connector, file.hash, name);
             }
             EntryData::Directory(_) | EntryData::NestedArchive(_) if entry.is_empty_directory() => {
                 println!("{}{}{}/  {}", prefix, connector, name, "<empty>".dimmed());
@@ -90,13 +76,23 @@ fn print_tree_inner(
                 _ => {
                     println!("{}{}{}/", prefix, connector, name);
                     let child_prefix = format!("{}{}", prefix, child_prefix);
-                    print_tree_inner(children, algo, recurse, &child_prefix, None);
+                    print_tree_inner(children, recurse, &child_prefix, None);
                 }
             },
             EntryData::NestedArchive(children) => {
                 println!("{}{}{}", prefix, connector, name);
                 let child_prefix = format!("{}{}", prefix, child_prefix);
-                print_tree_inner(children, algo, recurse, &child_prefix, None);
+                print_tree_inner(children, recurse, &child_prefix, None);
+            }
+            EntryData::Symlink(target) => {
+                println!(
+                    "{}{}{} -> {}  {}",
+                    prefix,
+                    connector,
+                    name,
+                    target.display(),
+                    "<symlink>".dimmed()
+                );
             }
         }
     }
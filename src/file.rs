@@ -2,14 +2,63 @@
 //!
 //! Infer type of archive, if valid ([`ArchiveType`]), and associated error reporting
 
-use crate::archive::ArchiveType;
+use crate::{archive::ArchiveType, decompress::HANDLERS};
+use infer::{Infer, MatcherType, Type};
+use memmap2::Mmap;
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read},
     path::Path,
     str::FromStr,
+    sync::OnceLock,
 };
-use strum::IntoEnumIterator;
+
+static INFERRER: OnceLock<Infer> = OnceLock::new();
+
+// `infer` does not know about every format we support, so we register custom matchers
+// for those it does not.  This is built once, as every entry of every archive (nested
+// archives being found this way) is checked with it, often from several threads at once
+fn inferrer() -> &'static Infer {
+    INFERRER.get_or_init(|| {
+        let mut info = Infer::new();
+        for matcher in HANDLERS.iter().flat_map(|handler| handler.matchers()) {
+            info.add(matcher.mime_type, matcher.extension, matcher.matches);
+        }
+        info
+    })
+}
+
+// `infer` only reads the first 8 KiB of a file, but some formats have their magic
+// bytes further in (such as ISO 9660, whose first volume descriptor is at 32 KiB)
+const SNIFF_LEN: u64 = 0x8800;
+
+fn infer_from_path(path: &Path) -> Option<Type> {
+    let mut buf = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(SNIFF_LEN).read_to_end(&mut buf))
+        .expect("file should be readable");
+    inferrer().get(&buf)
+}
+
+// Map a file into memory, so that it is read (by the OS, as it is accessed) without being
+// copied into a buffer of our own
+pub fn map_file(path: &Path) -> io::Result<Mmap> {
+    let file = File::open(path)?;
+
+    // SAFETY: the mapping is only valid while no other process modifies (or truncates) the
+    // file, which we assume of the archives we are given, as other archive tools do
+    unsafe { Mmap::map(&file) }
+}
+
+// Some formats have no magic bytes at all (such as Brotli), so the best we can do is
+// to identify them by their file extension
+pub fn archive_type_from_extension(path: &Path) -> Option<ArchiveType> {
+    let ext = path.extension()?.to_str()?;
+    HANDLERS
+        .iter()
+        .find(|handler| handler.extensions().contains(&ext))
+        .map(|handler| handler.archive_type())
+}
 
 struct PathValid {
     is_valid: bool,
@@ -42,24 +91,35 @@ impl From<&String> for PathValid {
         }
 
         // Check that the file provided is indeed a supported archive
-        let kind = infer::get_from_path(path).expect("file should be readable");
+        let kind = infer_from_path(path);
+
+        // Formats without magic bytes can only be identified by extension
+        if kind.is_none_or(|k| ArchiveType::from_str(k.mime_type()).is_err())
+            && archive_type_from_extension(path).is_some()
+        {
+            return PathValid::valid();
+        }
 
         if let Some(kind) = kind {
-            // Valid file to extract if the matcher type is an archive
-            if kind.matcher_type() == infer::MatcherType::Archive {
-                if ArchiveType::from_str(kind.mime_type()).is_ok() {
-                    PathValid::valid()
-                } else {
-                    let supported_archive_types = ArchiveType::iter()
-                        .map(String::from)
-                        .collect::<Vec<_>>()
-                        .join(", ");
-                    PathValid::invalid(&format!(
-                        "unsupported archive type \"{}\"; supported types: {}",
-                        kind.mime_type(),
-                        supported_archive_types,
-                    ))
-                }
+            // Valid file to extract if we support its type.  This is not necessarily
+            // an archive according to `infer`, as many documents (such as Office files)
+            // are archives in disguise
+            if ArchiveType::from_str(kind.mime_type()).is_ok() {
+                PathValid::valid()
+            } else if matches!(
+                kind.matcher_type(),
+                MatcherType::Archive | MatcherType::Custom
+            ) {
+                let supported_archive_types = HANDLERS
+                    .iter()
+                    .map(|handler| handler.name())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                PathValid::invalid(&format!(
+                    "unsupported archive type \"{}\"; supported types: {}",
+                    kind.mime_type(),
+                    supported_archive_types,
+                ))
             } else {
                 PathValid::invalid(&format!("invalid file type \"{}\"", kind.mime_type()))
             }
@@ -94,15 +154,18 @@ pub fn path_invalid_reason(path: &String) -> String {
 //
 // Assumes path is valid and file is an archive type!  use path_is_valid to confirm
 pub fn archive_type(path: &String) -> ArchiveType {
-    let kind = infer::get_from_path(path)
-        .expect("file should be readable")
-        .expect("file type should be obtainable");
-    ArchiveType::from_str(kind.mime_type()).unwrap()
+    let path = Path::new(path);
+    infer_from_path(path)
+        .and_then(|kind| ArchiveType::from_str(kind.mime_type()).ok())
+        .or_else(|| archive_type_from_extension(path))
+        .expect("file type should be obtainable")
 }
 
 // Variant of `archive_type` from bytes, rather than file path
 pub fn archive_type_from_bytes(bytes: &[u8]) -> Option<ArchiveType> {
-    infer::get(bytes).and_then(|kind| ArchiveType::from_str(kind.mime_type()).ok())
+    inferrer()
+        .get(bytes)
+        .and_then(|kind| ArchiveType::from_str(kind.mime_type()).ok())
 }
 
 trait FileTypeInference {
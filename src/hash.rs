@@ -1,6 +1,6 @@
 //! Compute hash
 //!
-//! Based on hashing algorithm (see [`HashAlgo`]), compute the hash of some given data
+//! Based on hashing algorithm (see [`HashAlgo`]), compute the hash of some given data.  Archive entries are hashed incrementally as they are decompressed (see [`FileData`](crate::archive::FileData)), so that the whole entry need not be in memory at once
 
 use crate::algo::HashAlgo;
 use digest::Digest;
@@ -9,7 +9,7 @@ use sha1::Sha1;
 use sha2::Sha256;
 
 // https://stackoverflow.com/q/64326373/
-fn compute_hash<D: Digest>(data: &Vec<u8>) -> String
+pub fn compute_hash<D: Digest>(data: &[u8]) -> String
 where
     D::OutputSize: std::ops::Add,
     <D::OutputSize as std::ops::Add>::Output: digest::generic_array::ArrayLength<u8>,
@@ -20,10 +20,35 @@ where
     format!("{:x}", hash)
 }
 
-pub fn get_hash_from_data(data: &Vec<u8>, algo: &HashAlgo) -> String {
-    match algo {
-        HashAlgo::Md5 => compute_hash::<Md5>(data),
-        HashAlgo::Sha1 => compute_hash::<Sha1>(data),
-        HashAlgo::Sha256 => compute_hash::<Sha256>(data),
+// Hash data incrementally, as it is read
+pub enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algo: &HashAlgo) -> Self {
+        match algo {
+            HashAlgo::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgo::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Md5(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+        }
     }
 }
@@ -1,18 +1,22 @@
 mod algo;
 mod archive;
+mod config;
 mod decompress;
 mod display;
 mod file;
 mod hash;
 mod recurse;
 mod tree;
+mod volume;
 
 use algo::HashAlgo;
 use clap::{ArgAction, Parser, crate_authors, crate_name, crate_version};
+use colored::Colorize;
 use std::{
     env,
     ffi::OsStr,
     io::{self, IsTerminal},
+    ops::Deref,
     path::Path,
     process,
 };
@@ -74,13 +78,85 @@ struct Cli {
         default_value_t = false,
     )]
     tree: bool,
+
+    /// Squash container image layers
+    ///
+    /// Container images (such as from `docker save`) are listed by layer by default.  Instead, apply each layer in order (including whiteout files, which remove files from the layers below) to show the image's final filesystem
+    #[arg(
+        long = "squash",
+        action = ArgAction::SetTrue,
+        default_value_t = false,
+    )]
+    squash: bool,
+
+    /// List the members of multi-member gzip files separately
+    ///
+    /// Gzip files may be made of several members (gzip streams) one after another, such as from concatenating gzip files, which are read as one stream by default (as `gzip -d` does).  Instead, list each member as a file of its own, named by its header where present
+    #[arg(
+        long = "gzip-members",
+        action = ArgAction::SetTrue,
+        default_value_t = false,
+    )]
+    gzip_members: bool,
+
+    /// Largest entry to keep in memory
+    ///
+    /// Entries are hashed as they are decompressed, and are only kept in memory (to be read again, such as when recursing into nested archives, reading container image layers, or verifying packages) if they are no larger than this.  Accepts a size in bytes, or with a K, M, G, or T suffix
+    #[arg(
+        long = "buffer-limit",
+        action = ArgAction::Set,
+        value_name = "size",
+        value_parser = config::parse_size,
+        default_value = "256M",
+    )]
+    buffer_limit: u64,
+
+    /// Number of threads to decompress and hash with
+    ///
+    /// Zip entries, and sibling nested archives, are decompressed and hashed in parallel (unless encrypted, as passwords are prompted for one at a time).  The output is listed in the same order regardless.  Defaults to the number of CPUs
+    #[arg(
+        long = "jobs",
+        short = 'j',
+        action = ArgAction::Set,
+        value_name = "threads",
+        default_value_t = 0,
+        hide_default_value = true,
+    )]
+    jobs: usize,
 }
 
 fn main() {
     let cli = Cli::parse();
+    config::init(config::Config {
+        hash: cli.hash,
+        buffer_limit: cli.buffer_limit,
+        gzip_members: cli.gzip_members,
+        squash: cli.squash,
+    });
+
+    // Zero threads means one per CPU
+    if let Err(e) = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.jobs)
+        .build_global()
+    {
+        eprintln!("[ERROR] Failed to start worker threads: {e}");
+        process::exit(1);
+    }
+
+    // Split archives are joined in memory, so that they are read as one archive
+    let file_path = Path::new(&cli.file_path);
+    let split_archive = volume::read_split_archive(file_path).map(|bytes| {
+        match file::archive_type_from_bytes(&bytes) {
+            Some(archive_type) => (bytes, archive_type),
+            None => {
+                eprintln!("[ERROR] File is not a valid input: unsupported split archive type");
+                process::exit(1);
+            }
+        }
+    });
 
     // Check that file is valid
-    if !file::path_is_valid(&cli.file_path) {
+    if split_archive.is_none() && !file::path_is_valid(&cli.file_path) {
         eprintln!(
             "[ERROR] File is not a valid input: {}",
             file::path_invalid_reason(&cli.file_path)
@@ -105,10 +181,27 @@ fn main() {
         cli.recurse.min(3)
     };
 
-    // Extract archive entries from input
-    let file_path = Path::new(&cli.file_path);
-    let archive_type = file::archive_type(&cli.file_path);
-    let entries = archive::get_file_data_from_archive(&cli.file_path, archive_type);
+    // Extract archive entries from input.  The input file is mapped into memory, so that it
+    // is read through the same (buffer-based) code path as nested archives, without being
+    // copied.  A split archive's joined volumes are read just as the input file would be
+    let (bytes, archive_type): (Box<dyn Deref<Target = [u8]>>, _) = match split_archive {
+        Some((bytes, archive_type)) => (Box::new(bytes), archive_type),
+        None => {
+            let bytes = file::map_file(file_path).unwrap_or_else(|e| {
+                eprintln!("[ERROR] Failed to read file: {e}");
+                process::exit(1);
+            });
+            (Box::new(bytes), file::archive_type(&cli.file_path))
+        }
+    };
+    let origin = archive::Origin::Input(file_path);
+    let archive::Archive {
+        entries,
+        container_kind,
+    } = archive::get_file_data(&bytes, archive_type, origin).unwrap_or_else(|| {
+        eprintln!("[ERROR] Failed to read archive");
+        process::exit(1);
+    });
 
     // Expand nested archives if recursion flag is sufficiently large
     let entries = if recurse > 1 {
@@ -119,14 +212,20 @@ fn main() {
 
     // Display output
     if cli.tree {
-        display::print_tree(
-            &entries,
-            &cli.hash,
-            recurse,
-            file_path.file_name().and_then(OsStr::to_str),
-        );
+        let label =
+            file_path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .map(|name| match container_kind {
+                    Some(kind) => format!("{}  {}", name, format!("<{kind}>").dimmed()),
+                    None => String::from(name),
+                });
+        display::print_tree(&entries, recurse, label.as_deref());
     } else {
-        display::print_table(&entries, &cli.hash, recurse);
+        if let Some(kind) = container_kind {
+            eprintln!("[INFO] Container type: {kind}");
+        }
+        display::print_table(&entries, recurse);
     }
 
     process::exit(0);
@@ -1,7 +1,8 @@
 use crate::{
-    archive::{self, ArchiveEntry, ArchiveType, EntryData},
+    archive::{self, ArchiveEntry, EntryData, Origin},
     file,
 };
+use rayon::prelude::*;
 use std::path::Path;
 
 pub fn expand_nested_archives(
@@ -10,70 +11,100 @@ pub fn expand_nested_archives(
     context: &Path,
 ) -> Vec<ArchiveEntry> {
     let allow_nested_encryption = recurse_level >= 3;
+    let expand = |entry| expand_nested_entry(entry, recurse_level, context);
 
-    entries
-        .into_iter()
-        .map(|entry| match entry.data {
-            EntryData::File(bytes) => {
-                let Some(archive_type) = file::archive_type_from_bytes(&bytes) else {
-                    return ArchiveEntry {
-                        path: entry.path,
-                        data: EntryData::File(bytes),
-                    };
+    // Sibling entries are expanded in parallel (keeping their order), unless we may need to
+    // prompt for passwords, which must be asked for one at a time
+    if allow_nested_encryption {
+        entries.into_iter().map(expand).collect()
+    } else {
+        entries.into_par_iter().map(expand).collect()
+    }
+}
+
+fn expand_nested_entry(entry: ArchiveEntry, recurse_level: u8, context: &Path) -> ArchiveEntry {
+    let allow_nested_encryption = recurse_level >= 3;
+
+    match entry.data {
+        EntryData::File(mut data) => {
+            let entry_context = match &entry.path {
+                Some(p) => context.join(p),
+                None => context.to_path_buf(),
+            };
+
+            // The content is no longer needed once we know whether this is an archive,
+            // so it is taken (and dropped, if not), leaving only the hash
+            let Some(bytes) = data.bytes.take() else {
+                // Entries too large to keep in memory were only hashed as they were
+                // read, so cannot be expanded
+                let archive_type = data.dropped_archive_type.or_else(|| {
+                    entry
+                        .path
+                        .as_deref()
+                        .and_then(file::archive_type_from_extension)
+                });
+                if archive_type.is_some() {
+                    eprintln!(
+                        "[WARN] Skipping nested archive (too large to keep in memory; see --buffer-limit): {}",
+                        entry_context.display()
+                    );
+                }
+                return ArchiveEntry {
+                    path: entry.path,
+                    data: EntryData::File(data),
                 };
+            };
 
-                let entry_context = match &entry.path {
-                    Some(p) => context.join(p),
-                    None => context.to_path_buf(),
+            // Formats without magic bytes can only be identified by the entry's name
+            let archive_type = file::archive_type_from_bytes(&bytes).or_else(|| {
+                entry
+                    .path
+                    .as_deref()
+                    .and_then(file::archive_type_from_extension)
+            });
+            let Some(archive_type) = archive_type else {
+                return ArchiveEntry {
+                    path: entry.path,
+                    data: EntryData::File(data),
                 };
+            };
 
-                match archive::get_file_data_from_bytes(
-                    bytes,
-                    archive_type,
-                    allow_nested_encryption,
-                    &entry_context,
-                ) {
-                    Ok(nested) => ArchiveEntry {
-                        path: entry.path,
-                        data: EntryData::NestedArchive(expand_nested_archives(
-                            nested,
-                            recurse_level,
-                            &entry_context,
-                        )),
-                    },
-                    Err(bytes) => {
-                        let reason = if matches!(archive_type, ArchiveType::Rar) {
-                            "RAR recursion unsupported without writing to disk"
-                        } else {
-                            "incorrect, missing, or disallowed password"
-                        };
-                        eprintln!(
-                            "[WARN] Skipping nested archive ({reason}): {}",
-                            entry_context.display()
-                        );
-                        ArchiveEntry {
-                            path: entry.path,
-                            data: EntryData::File(bytes),
-                        }
-                    }
-                }
+            let origin = Origin::Nested {
+                context: &entry_context,
+                allow_encryption: allow_nested_encryption,
+            };
+            match archive::get_file_data(&bytes, archive_type, origin) {
+                Some(nested) => ArchiveEntry {
+                    path: entry.path,
+                    data: EntryData::NestedArchive(expand_nested_archives(
+                        nested.entries,
+                        recurse_level,
+                        &entry_context,
+                    )),
+                },
+                // The archive was not expanded (having warned why, if worth warning about), so
+                // is listed as a file
+                None => ArchiveEntry {
+                    path: entry.path,
+                    data: EntryData::File(data),
+                },
             }
-            EntryData::Directory(children) => ArchiveEntry {
-                path: entry.path,
-                data: EntryData::Directory(expand_nested_archives(
-                    children,
-                    recurse_level,
-                    context,
-                )),
-            },
-            EntryData::NestedArchive(children) => ArchiveEntry {
-                path: entry.path,
-                data: EntryData::NestedArchive(expand_nested_archives(
-                    children,
-                    recurse_level,
-                    context,
-                )),
-            },
-        })
-        .collect()
+        }
+        EntryData::Directory(children) => ArchiveEntry {
+            path: entry.path,
+            data: EntryData::Directory(expand_nested_archives(children, recurse_level, context)),
+        },
+        EntryData::NestedArchive(children) => ArchiveEntry {
+            path: entry.path,
+            data: EntryData::NestedArchive(expand_nested_archives(
+                children,
+                recurse_level,
+                context,
+            )),
+        },
+        EntryData::Symlink(target) => ArchiveEntry {
+            path: entry.path,
+            data: EntryData::Symlink(target),
+        },
+    }
 }
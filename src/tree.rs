@@ -19,7 +19,20 @@ pub fn build_tree(flat: Vec<ArchiveEntry>) -> Vec<ArchiveEntry> {
             }
         };
 
-        let components: Vec<Component> = path.components().collect();
+        // Archive paths are often relative to the archive root (e.g., "./usr/bin" in
+        // Debian packages) or absolute, so only the normal components are kept.  The root
+        // directory entry itself ("./") is then redundant
+        let components: Vec<Component> = path
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        if components.is_empty() {
+            if !matches!(entry.data, EntryData::Directory(_)) {
+                root.push(entry);
+            }
+            continue;
+        }
+
         insert_entry(&mut root, &components, entry);
     }
 
@@ -71,3 +84,17 @@ fn insert_entry(children: &mut Vec<ArchiveEntry>, components: &[Component], entr
 
     children.push(implicit_dir);
 }
+
+// Find an entry in the (tree-structured) archive by its slash-separated path
+pub fn find_entry<'a>(entries: &'a [ArchiveEntry], path: &str) -> Option<&'a ArchiveEntry> {
+    let (first, rest) = match path.split_once('/') {
+        Some((first, rest)) => (first, Some(rest)),
+        None => (path, None),
+    };
+    let entry = entries.iter().find(|entry| entry.name() == first)?;
+    match (rest, &entry.data) {
+        (None, _) | (Some(""), _) => Some(entry),
+        (Some(rest), EntryData::Directory(children)) => find_entry(children, rest),
+        _ => None,
+    }
+}
@@ -0,0 +1,298 @@
+//! Handle split and multi-volume archives
+//!
+//! Large archives are often split into several files.  Given the first volume, we locate its siblings and join them in memory, so that they can be read as a single archive:
+//!   - Numbered parts (`.001`, `.002`, ...; including `.7z.001`), which are simply concatenated;
+//!   - Split zip archives (`.z01`, `.z02`, ..., `.zip`), whose offsets are relative to the volume they are in, and so must be adjusted once joined.
+//!
+//! Multi-volume RAR archives (`.part1.rar`, `.part2.rar`, ...) are not joined here, as each volume is an archive in its own right; instead, we locate the volumes to be read in turn (see [`rar`](crate::decompress::rar)).  The volumes of older RAR archives (`.rar`, `.r00`, ...) are located by the RAR library itself.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+// Zip end of central directory records (and their Zip64 counterparts), and central
+// directory headers
+//   <pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+const ZIP_EOCD_MAGIC: &[u8] = b"PK\x05\x06";
+const ZIP_EOCD_LEN: usize = 22;
+const ZIP64_EOCD_LOCATOR_MAGIC: &[u8] = b"PK\x06\x07";
+const ZIP64_EOCD_LOCATOR_LEN: usize = 20;
+const ZIP64_EOCD_MAGIC: &[u8] = b"PK\x06\x06";
+const ZIP_CENTRAL_DIRECTORY_MAGIC: &[u8] = b"PK\x01\x02";
+const ZIP_CENTRAL_DIRECTORY_LEN: usize = 46;
+const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+
+// Values of fields too large for their place in the record, which are instead found in
+// the Zip64 record or extra field
+const ZIP64_U16: u16 = 0xffff;
+const ZIP64_U32: u32 = 0xffff_ffff;
+
+// Returns the paths of each volume in order if the given path is the first of a split
+// archive of more than one volume
+fn find_volumes(path: &Path) -> Option<Vec<PathBuf>> {
+    let extension = path.extension()?.to_str()?;
+    let with_extension = |ext: String| path.with_extension(ext);
+
+    // Numbered parts: "name.001", "name.002", ...
+    if extension == "001" {
+        let volumes = (1..)
+            .map(|i| with_extension(format!("{i:03}")))
+            .take_while(|p| p.is_file())
+            .collect::<Vec<_>>();
+        return (volumes.len() > 1).then_some(volumes);
+    }
+
+    // Split zip: "name.z01", "name.z02", ..., and finally "name.zip".  Either the first
+    // volume or the last (which is the one named as a zip archive) may be given
+    let is_upper = match extension {
+        "zip" | "z01" => false,
+        "ZIP" | "Z01" => true,
+        _ => return None,
+    };
+    let case = |ext: String| {
+        if is_upper { ext.to_uppercase() } else { ext }
+    };
+    let mut volumes = (1..)
+        .map(|i| with_extension(case(format!("z{i:02}"))))
+        .take_while(|p| p.is_file())
+        .collect::<Vec<_>>();
+    let last = with_extension(case(String::from("zip")));
+    if volumes.is_empty() || !last.is_file() {
+        return None;
+    }
+    volumes.push(last);
+    Some(volumes)
+}
+
+// Returns the paths of each volume of a multi-volume RAR archive in order, if the given
+// path is its first volume ("name.part1.rar", or with the part number zero-padded)
+pub fn find_rar_volumes(path: &Path) -> Option<Vec<PathBuf>> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, rest) = name.rsplit_once(".part")?;
+    let (number, extension) = rest.split_once('.')?;
+    if !extension.eq_ignore_ascii_case("rar")
+        || number.is_empty()
+        || !number.bytes().all(|b| b.is_ascii_digit())
+        || number.parse::<usize>() != Ok(1)
+    {
+        return None;
+    }
+
+    let width = number.len();
+    let volumes = (1..)
+        .map(|i| path.with_file_name(format!("{stem}.part{i:0width$}.{extension}")))
+        .take_while(|p| p.is_file())
+        .collect();
+    Some(volumes)
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Result<u16, String> {
+    buf.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| String::from("truncated record"))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<u32, String> {
+    buf.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| String::from("truncated record"))
+}
+
+fn read_u64(buf: &[u8], pos: usize) -> Result<u64, String> {
+    buf.get(pos..pos + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| String::from("truncated record"))
+}
+
+fn write_u16(buf: &mut [u8], pos: usize, value: u16) {
+    buf[pos..pos + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(buf: &mut [u8], pos: usize, value: u32) {
+    buf[pos..pos + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(buf: &mut [u8], pos: usize, value: u64) {
+    buf[pos..pos + 8].copy_from_slice(&value.to_le_bytes());
+}
+
+// Offsets in a split zip archive are relative to the start of the volume (or "disk")
+// they are in.  Having joined the volumes, we rewrite these to be relative to the start
+// of the joined archive, as if it had been written to a single disk
+fn fix_split_zip_offsets(buf: &mut [u8], disk_offsets: &[u64]) -> Result<(), String> {
+    let disk_offset = |disk: u32| {
+        disk_offsets
+            .get(disk as usize)
+            .copied()
+            .ok_or_else(|| format!("reference to missing volume {}", disk + 1))
+    };
+
+    // The end of central directory record is at the end of the last volume, followed by
+    // a comment of at most 64 KiB
+    let search_start = buf
+        .len()
+        .saturating_sub(ZIP_EOCD_LEN + usize::from(u16::MAX));
+    let eocd = buf[search_start..]
+        .windows(ZIP_EOCD_MAGIC.len())
+        .rposition(|w| w == ZIP_EOCD_MAGIC)
+        .map(|pos| search_start + pos)
+        .ok_or_else(|| String::from("missing end of central directory record"))?;
+
+    let mut n_entries = u64::from(read_u16(buf, eocd + 10)?);
+    let mut cd_disk = u32::from(read_u16(buf, eocd + 6)?);
+    let mut cd_offset = u64::from(read_u32(buf, eocd + 16)?);
+
+    // The Zip64 end of central directory record, if any, is found from the locator
+    // immediately preceding the end of central directory record
+    let zip64_eocd = match eocd.checked_sub(ZIP64_EOCD_LOCATOR_LEN) {
+        Some(locator) if buf[locator..].starts_with(ZIP64_EOCD_LOCATOR_MAGIC) => {
+            let record = disk_offset(read_u32(buf, locator + 4)?)? + read_u64(buf, locator + 8)?;
+            let record = usize::try_from(record).map_err(|e| e.to_string())?;
+            if !buf
+                .get(record..)
+                .is_some_and(|b| b.starts_with(ZIP64_EOCD_MAGIC))
+            {
+                return Err(String::from(
+                    "invalid Zip64 end of central directory locator",
+                ));
+            }
+
+            n_entries = read_u64(buf, record + 32)?;
+            cd_disk = read_u32(buf, record + 20)?;
+            cd_offset = read_u64(buf, record + 48)?;
+
+            write_u32(buf, locator + 4, 0);
+            write_u64(buf, locator + 8, record as u64);
+            write_u32(buf, locator + 16, 1);
+            Some(record)
+        }
+        _ => None,
+    };
+
+    let cd_start = disk_offset(cd_disk)? + cd_offset;
+
+    // Rewrite each central directory header's local header offset, which may be in its
+    // Zip64 extra field
+    let mut pos = usize::try_from(cd_start).map_err(|e| e.to_string())?;
+    for _ in 0..n_entries {
+        if !buf
+            .get(pos..)
+            .is_some_and(|b| b.starts_with(ZIP_CENTRAL_DIRECTORY_MAGIC))
+        {
+            return Err(format!("invalid central directory header at offset {pos}"));
+        }
+
+        let name_len = usize::from(read_u16(buf, pos + 28)?);
+        let extra_len = usize::from(read_u16(buf, pos + 30)?);
+        let comment_len = usize::from(read_u16(buf, pos + 32)?);
+        let disk = read_u16(buf, pos + 34)?;
+        let offset = read_u32(buf, pos + 42)?;
+
+        // Zip64 extra field contains only the values which did not fit in the header,
+        // in this order
+        let mut zip64_offset_pos = None;
+        let mut zip64_disk_pos = None;
+        let mut extra = pos + ZIP_CENTRAL_DIRECTORY_LEN + name_len;
+        let extra_end = extra + extra_len;
+        while extra + 4 <= extra_end {
+            let id = read_u16(buf, extra)?;
+            let size = usize::from(read_u16(buf, extra + 2)?);
+            if id == ZIP64_EXTRA_FIELD_ID {
+                let mut field = extra + 4;
+                for value in [read_u32(buf, pos + 24)?, read_u32(buf, pos + 20)?] {
+                    if value == ZIP64_U32 {
+                        field += 8;
+                    }
+                }
+                if offset == ZIP64_U32 {
+                    zip64_offset_pos = Some(field);
+                    field += 8;
+                }
+                if disk == ZIP64_U16 {
+                    zip64_disk_pos = Some(field);
+                }
+            }
+            extra += 4 + size;
+        }
+
+        let disk = match zip64_disk_pos {
+            Some(field) => read_u32(buf, field)?,
+            None => u32::from(disk),
+        };
+        let offset = match zip64_offset_pos {
+            Some(field) => read_u64(buf, field)?,
+            None => u64::from(offset),
+        };
+        let offset = disk_offset(disk)? + offset;
+
+        match zip64_offset_pos {
+            Some(field) => write_u64(buf, field, offset),
+            None => write_u32(
+                buf,
+                pos + 42,
+                u32::try_from(offset).map_err(|_| "offset too large for zip record")?,
+            ),
+        }
+        match zip64_disk_pos {
+            Some(field) => write_u32(buf, field, 0),
+            None => write_u16(buf, pos + 34, 0),
+        }
+
+        pos = extra_end + comment_len;
+    }
+
+    // Finally, the end of central directory records are rewritten to describe a single
+    // disk
+    if let Some(record) = zip64_eocd {
+        write_u32(buf, record + 16, 0);
+        write_u32(buf, record + 20, 0);
+        write_u64(buf, record + 24, n_entries);
+        write_u64(buf, record + 48, cd_start);
+    }
+    write_u16(buf, eocd + 4, 0);
+    write_u16(buf, eocd + 6, 0);
+    if read_u16(buf, eocd + 8)? != ZIP64_U16 {
+        write_u16(buf, eocd + 8, read_u16(buf, eocd + 10)?);
+    }
+    if read_u32(buf, eocd + 16)? != ZIP64_U32 {
+        write_u32(
+            buf,
+            eocd + 16,
+            u32::try_from(cd_start).map_err(|_| "offset too large for zip record")?,
+        );
+    }
+
+    Ok(())
+}
+
+// Returns the joined contents of a split archive, if the given path is the first volume
+// of one (or the last volume, in the case of split zip archives)
+pub fn read_split_archive(path: &Path) -> Option<Vec<u8>> {
+    let volumes = find_volumes(path)?;
+
+    let mut joined = Vec::new();
+    let mut disk_offsets = Vec::with_capacity(volumes.len());
+    for volume in &volumes {
+        disk_offsets.push(joined.len() as u64);
+        match fs::read(volume) {
+            Ok(bytes) => joined.extend(bytes),
+            Err(e) => {
+                eprintln!("[ERROR] Failed to read volume {}: {e}", volume.display());
+                process::exit(1);
+            }
+        }
+    }
+
+    let is_split_zip = volumes
+        .last()
+        .and_then(|p| p.extension())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    if is_split_zip && let Err(e) = fix_split_zip_offsets(&mut joined, &disk_offsets) {
+        eprintln!("[ERROR] Failed to join split zip archive: {e}");
+        process::exit(1);
+    }
+
+    Some(joined)
+}
@@ -1,7 +1,10 @@
 #!/usr/bin/env bash
 
-# End-to-end test suite, testing the functionality of the entire programme
-# for different input types (zip and 7z)
+# End-to-end test suite, testing the functionality of the entire programme for different
+# input types (zip, split zip, JAR, EPUB, 7z, RAR, gzip, zstd, lz4, Brotli, xz,
+# compress, ar, cpio, RPM, ISO 9660, cabinet, OLE/MSI, email, Outlook message, PDF,
+# container image (Docker and OCI layouts), npm package, Python wheel, Ruby gem, Rust
+# crate, self-extracting archives, and archives split into numbered parts)
 #
 # TODO: Does not yet test input files with more complex file heirarchy
 # TODO: Add Rust unit tests
@@ -27,23 +30,410 @@ FILE_ZIP="${FILE}.zip"
 FILE_ZIP_P="${FILE%.*}.pass.${FILE_BASE##*.}.zip"
 FILE_7Z="${FILE}.7z"
 FILE_7Z_P="${FILE%.*}.pass.${FILE_BASE##*.}.7z"
+FILE_ZST="${FILE}.zst"
+FILE_TAR_ZST="${FILE}.tar.zst"
+FILE_LZ4="${FILE}.lz4"
+FILE_TAR_LZ4="${FILE}.tar.lz4"
+FILE_Z="${FILE}.Z"
+FILE_TAR_Z="${FILE}.tar.Z"
+
+# The LZW decoder is written by hand, so we also test it on a larger file, to exercise
+# code width changes (up to 16 bits, or 12 bits with `-b 12`), table resets (CLEAR codes,
+# once the table is full and incompressible data follows), and codes referring to the
+# entry being defined (KwKwK, from long runs of one byte)
+FILE_LZW="${FILE%.*}.lzw.${FILE_BASE##*.}"
+FILE_LZW_Z="${FILE_LZW}.Z"
+FILE_LZW_Z12="${FILE_LZW}.b12.Z"
+FILE_AR="${FILE}.a"
+FILE_DEB="${FILE%.*}.deb"
+FILE_LINK="${FILE%.*}.link.${FILE_BASE##*.}"
+FILE_CPIO="${FILE}.cpio"
+FILE_CPIO_XZ="${FILE}.cpio.xz"
+FILE_RPM="${FILE%.*}.rpm"
+FILE_RPM_TAMPERED="${FILE%.*}.tampered.rpm"
+FILE_CAB="${FILE}.cab"
+FILE_CAB_Z="${FILE%.*}.mszip.${FILE_BASE##*.}.cab"
+# gcab only writes stored and MSZIP folders, and LZX cabinets come from makecab on
+# Windows, so this one (of the test file and `seq 1 12000`) is checked in
+FILE_CAB_LZX="tests/data/test-xmemhash.lzx.cab"
+FILE_EML="${FILE%.*}.eml"
+FILE_EML_ZIP="${FILE_EML}.zip"
+FILE_RAR="${FILE}.rar"
+FILE_RAR_M5="${FILE%.*}.m5.${FILE_BASE##*.}.rar"
+FILE_RAR_SOLID="${FILE%.*}.solid.${FILE_BASE##*.}.rar"
+FILE_RAR_E8="${FILE%.*}.e8.${FILE_BASE##*.}.rar"
+FILE_RAR_DELTA="${FILE%.*}.delta.${FILE_BASE##*.}.rar"
+FILE_RAR_P="${FILE%.*}.pass.${FILE_BASE##*.}.rar"
+FILE_RAR_HP="${FILE%.*}.hpass.${FILE_BASE##*.}.rar"
+FILE_RAR_VOL="${FILE_LZW%.*}.rar"
+FILE_XZ="${FILE}.xz"
+FILE_TAR_XZ="${FILE}.tar.xz"
+FILE_SYMLINK="${FILE%.*}.symlink.${FILE_BASE##*.}"
+FILE_CPIO_ODC="${FILE%.*}.odc.${FILE_BASE##*.}.cpio"
+FILE_ISO="${FILE%.*}.iso"
+FILE_ISO_JR="${FILE%.*}.joliet.iso"
+FILE_MSI="${FILE%.*}.msi"
+FILE_JAR="${FILE%.*}.jar"
+FILE_EPUB="${FILE%.*}.epub"
+FILE_SFX="${FILE%.*}.sfx"
+FILE_SFX_A="${FILE%.*}.adjusted.sfx"
+FILE_LZW_ZIP="${FILE_LZW}.zip"
+FILE_SPLIT_ZIP="${FILE_LZW%.*}.split.zip"
+# No common tool writes Outlook messages, so this one (with the test file attached) is
+# checked in
+FILE_MSG="tests/data/test-xmemhash.msg"
+FILE_PDF="${FILE%.*}.pdf"
+FILE_IMAGE="${FILE%.*}.image.tar"
+FILE_NPM="${FILE%.*}-1.0.0.tgz"
+FILE_WHEEL="${FILE%.*}-1.0-py3-none-any.whl"
+FILE_RAR4="${FILE%.*}.rar4.${FILE_BASE##*.}.rar"
+FILE_RAR4_ZIP="${FILE_RAR4}.zip"
+FILE_GZ_RENAMED="${FILE%.*}.renamed.gz"
+FILE_GZ_MEMBERS="${FILE%.*}.members.gz"
+# Brotli streams have no magic bytes, so are identified by their extension
+FILE_BR="${FILE}.br"
+FILE_TAR_BR="${FILE}.tar.br"
+FILE_GEM="${FILE%.*}-1.0.gem"
+FILE_GEM_TAMPERED="${FILE%.*}-1.0.tampered.gem"
+FILE_CRATE="${FILE%.*}-1.0.0.crate"
+FILE_OCI="${FILE%.*}.oci.tar"
+FILE_OCI_DEEP="${FILE%.*}.oci-deep.tar"
+FILE_NESTED_ZIP="${FILE%.*}.nested.zip"
 
 zip "$FILE_ZIP" "$FILE" > /dev/null
 zip -P infected "$FILE_ZIP_P" "$FILE" > /dev/null
 7z a "$FILE_7Z" "$FILE" > /dev/null
 7z a -pinfected "$FILE_7Z_P" "$FILE" > /dev/null
+zstd -q "$FILE" -o "$FILE_ZST"
+tar -cf - "$FILE" | zstd -q -o "$FILE_TAR_ZST"
+lz4 -q "$FILE" "$FILE_LZ4"
+tar -cf - "$FILE" | lz4 -q - "$FILE_TAR_LZ4"
+compress -c "$FILE" > "$FILE_Z"
+tar -cf - "$FILE" | compress -c > "$FILE_TAR_Z"
+{ seq 1 50000; head -c 5000 /dev/zero | tr '\0' 'a'; seq 1 200000 | gzip -n -9; } > "$FILE_LZW"
+compress -c "$FILE_LZW" > "$FILE_LZW_Z"
+compress -b 12 -c "$FILE_LZW" > "$FILE_LZW_Z12"
+ar rc "$FILE_AR" "$FILE"
+DEB_DIR="$(mktemp -d)"
+echo "2.0" > "$DEB_DIR/debian-binary"
+printf 'Package: test-xmemhash\nVersion: 1.0\n' > "$DEB_DIR/control"
+tar -czf "$DEB_DIR/control.tar.gz" -C "$DEB_DIR" control
+tar -cf - "$FILE" | xz -q > "$DEB_DIR/data.tar.xz"
+(cd "$DEB_DIR" && ar rc deb debian-binary control.tar.gz data.tar.xz)
+mv "$DEB_DIR/deb" "$FILE_DEB"
+rm -r "$DEB_DIR"
+# The newc format stores the content of hard links only with the last link
+ln "$FILE" "$FILE_LINK"
+printf '%s\n' "$FILE" "$FILE_LINK" | cpio -o -H newc --quiet > "$FILE_CPIO"
+xz -q -c "$FILE_CPIO" > "$FILE_CPIO_XZ"
+RPM_DIR="$(mktemp -d)"
+cat > "$RPM_DIR/test.spec" << SPEC
+Name: test-xmemhash
+Version: 1.0
+Release: 1
+Summary: xmemhash test package
+License: MIT
+BuildArch: noarch
+
+%description
+xmemhash test package
+
+%install
+mkdir -p %{buildroot}/opt
+cp "$PWD/$FILE" %{buildroot}/opt/
+
+%files
+/opt/$FILE
+SPEC
+rpmbuild -bb --quiet --define "_topdir $RPM_DIR" "$RPM_DIR/test.spec" > /dev/null
+mv "$RPM_DIR"/RPMS/noarch/*.rpm "$FILE_RPM"
+rm -r "$RPM_DIR"
+# The header records a different digest for the file than the payload holds
+RPM_DIGEST="$(sha256sum "$FILE" | cut -d ' ' -f 1)"
+sed "s/$RPM_DIGEST/$(echo "$RPM_DIGEST" | tr 0-9a-f 1-9a-f0)/" "$FILE_RPM" > "$FILE_RPM_TAMPERED"
+gcab -c "$FILE_CAB" "$FILE"
+gcab -c -z "$FILE_CAB_Z" "$FILE"
+{
+    printf 'MIME-Version: 1.0\nFrom: a@example.com\nTo: b@example.com\nSubject: xmemhash\n'
+    printf 'Content-Type: multipart/mixed; boundary="xmemhash"\n\n--xmemhash\n'
+    printf 'Content-Type: text/plain\n\nAttached.\n--xmemhash\n'
+    printf 'Content-Type: text/plain\nContent-Disposition: attachment; filename="%s"\n' "$FILE"
+    printf 'Content-Transfer-Encoding: base64\n\n'
+    base64 "$FILE"
+    printf -- '--xmemhash--\n'
+} > "$FILE_EML"
+zip "$FILE_EML_ZIP" "$FILE_EML" > /dev/null
+# RAR 5 archives, stored and compressed, solid, with forced x86 and delta filters,
+# encrypted (file data only, and headers too), and split into volumes
+rar a -m0 "$FILE_RAR" "$FILE" > /dev/null
+rar a -m5 "$FILE_RAR_M5" "$FILE" "$FILE_LZW" > /dev/null
+rar a -s "$FILE_RAR_SOLID" "$FILE" "$FILE_LZW" > /dev/null
+rar a -mcE+ "$FILE_RAR_E8" "$FILE" "$FILE_LZW" > /dev/null
+rar a -mcD+ "$FILE_RAR_DELTA" "$FILE" "$FILE_LZW" > /dev/null
+rar a -pinfected "$FILE_RAR_P" "$FILE" > /dev/null
+rar a -hpinfected "$FILE_RAR_HP" "$FILE" > /dev/null
+rar a -m0 -v200k "$FILE_RAR_VOL" "$FILE_LZW" > /dev/null
+xz -q -c "$FILE" > "$FILE_XZ"
+tar -cf - "$FILE" | xz -q > "$FILE_TAR_XZ"
+ln -s "$FILE" "$FILE_SYMLINK"
+printf '%s\n' "$FILE" "$FILE_SYMLINK" | cpio -o -H odc --quiet > "$FILE_CPIO_ODC"
+genisoimage -quiet -o "$FILE_ISO" "$FILE"
+genisoimage -quiet -J -R -o "$FILE_ISO_JR" "$FILE"
+# Installer packages keep their files in cabinets, stored as streams
+msibuild "$FILE_MSI" -a "$FILE" "$FILE" -a data.cab "$FILE_CAB_Z"
+# Java archives and EPUB e-books are zip archives, identified by their marker files
+ZIP_DIR="$(mktemp -d)"
+cp "$FILE" "$ZIP_DIR"
+mkdir "$ZIP_DIR/META-INF"
+printf 'Manifest-Version: 1.0\n' > "$ZIP_DIR/META-INF/MANIFEST.MF"
+(cd "$ZIP_DIR" && zip -q jar META-INF/MANIFEST.MF "$FILE")
+rm -r "$ZIP_DIR/META-INF"
+printf 'application/epub+zip' > "$ZIP_DIR/mimetype"
+(cd "$ZIP_DIR" && zip -q -X -0 epub mimetype && zip -q -X epub "$FILE")
+mv "$ZIP_DIR/jar.zip" "$FILE_JAR"
+mv "$ZIP_DIR/epub.zip" "$FILE_EPUB"
+rm -r "$ZIP_DIR"
+# A self-extracting archive is an executable with an archive appended, whose offsets
+# may be adjusted to be relative to the start of the file (with `zip -A`)
+cat "$(type -P true)" "$FILE_ZIP" > "$FILE_SFX"
+cp "$FILE_SFX" "$FILE_SFX_A"
+zip -q -A "$FILE_SFX_A"
+# Archives split into numbered parts (".001", ".002", ...) are simply concatenated, but
+# the offsets within each volume of a split zip archive (".z01", ..., ".zip") are
+# relative to that volume
+zip -q "$FILE_LZW_ZIP" "$FILE_LZW"
+split -b 100k -d -a 3 --numeric-suffixes=1 "$FILE_LZW_ZIP" "$FILE_LZW_ZIP."
+zip -q -s 100k "$FILE_SPLIT_ZIP" "$FILE_LZW"
+qpdf --empty "$FILE_PDF" --add-attachment "$FILE" --
+# A `docker save` image of two layers, the second of which removes a file of the first
+IMAGE_DIR="$(mktemp -d)"
+mkdir "$IMAGE_DIR/1" "$IMAGE_DIR/2" "$IMAGE_DIR/whiteout"
+tar -cf "$IMAGE_DIR/1/layer.tar" "$FILE" "$FILE_LZW"
+touch "$IMAGE_DIR/whiteout/.wh.$FILE_LZW"
+tar -cf "$IMAGE_DIR/2/layer.tar" -C "$IMAGE_DIR/whiteout" ".wh.$FILE_LZW"
+echo '{}' > "$IMAGE_DIR/config.json"
+printf '[{"Config":"config.json","RepoTags":["xmemhash:test"],"Layers":["1/layer.tar","2/layer.tar"]}]' \
+    > "$IMAGE_DIR/manifest.json"
+tar -cf "$FILE_IMAGE" -C "$IMAGE_DIR" manifest.json config.json 1 2
+rm -r "$IMAGE_DIR"
+# Language packages: an npm package, and a Python wheel, whose RECORD gives the digest
+# of each of its files
+PACKAGE_DIR="$(mktemp -d)"
+cp "$FILE" "$PACKAGE_DIR"
+printf '{"name": "%s", "version": "1.0.0"}\n' "${FILE%.*}" > "$PACKAGE_DIR/package.json"
+npm pack --quiet --pack-destination "$PWD" "$PACKAGE_DIR" > /dev/null
+rm "$PACKAGE_DIR/package.json"
+DIST_INFO="test_xmemhash-1.0.dist-info"
+mkdir "$PACKAGE_DIR/$DIST_INFO"
+printf 'Wheel-Version: 1.0\n' > "$PACKAGE_DIR/$DIST_INFO/WHEEL"
+printf 'Metadata-Version: 2.1\nName: test-xmemhash\nVersion: 1.0\n' > "$PACKAGE_DIR/$DIST_INFO/METADATA"
+for WHEEL_FILE in "$FILE" "$DIST_INFO/WHEEL" "$DIST_INFO/METADATA"; do
+    DIGEST="$(openssl dgst -sha256 -binary "$PACKAGE_DIR/$WHEEL_FILE" | base64 | tr '+/' '-_' | tr -d '=')"
+    echo "$WHEEL_FILE,sha256=$DIGEST,$(wc -c < "$PACKAGE_DIR/$WHEEL_FILE")"
+done > "$PACKAGE_DIR/$DIST_INFO/RECORD"
+echo "$DIST_INFO/RECORD,," >> "$PACKAGE_DIR/$DIST_INFO/RECORD"
+(cd "$PACKAGE_DIR" && zip -q -r wheel.zip "$FILE" "$DIST_INFO")
+mv "$PACKAGE_DIR/wheel.zip" "$FILE_WHEEL"
+rm -r "$PACKAGE_DIR"
+# RAR 4 archives are read by the RAR library from a file, which nested archives are
+# given in memory
+rar a -ma4 "$FILE_RAR4" "$FILE" > /dev/null
+zip "$FILE_RAR4_ZIP" "$FILE_RAR4" > /dev/null
+# The content of a gzip file is named from its header, rather than the file name, and
+# files of several members are read whole, unless listed member by member
+gzip -c "$FILE" > "$FILE_GZ_RENAMED"
+{ gzip -c "$FILE"; gzip -c "$FILE_LZW"; } > "$FILE_GZ_MEMBERS"
+brotli -c "$FILE" > "$FILE_BR"
+tar -cf - "$FILE" | brotli -c > "$FILE_TAR_BR"
+# A Ruby gem (laid out as `gem build` would), whose checksums give the digests of its
+# metadata and data, and one whose data has since been replaced
+GEM_DIR="$(mktemp -d)"
+printf -- '--- !ruby/object:Gem::Specification\nname: test-xmemhash\n' | gzip -n > "$GEM_DIR/metadata.gz"
+tar -cf - "$FILE" | gzip -n > "$GEM_DIR/data.tar.gz"
+{
+    printf -- '---\nSHA256:\n'
+    for GEM_FILE in metadata.gz data.tar.gz; do
+        printf '  %s: %s\n' "$GEM_FILE" "$(sha256sum "$GEM_DIR/$GEM_FILE" | cut -d ' ' -f 1)"
+    done
+} | gzip -n > "$GEM_DIR/checksums.yaml.gz"
+tar -cf "$FILE_GEM" -C "$GEM_DIR" metadata.gz data.tar.gz checksums.yaml.gz
+tar -cf - "$FILE_LZW" | gzip -n > "$GEM_DIR/data.tar.gz"
+tar -cf "$FILE_GEM_TAMPERED" -C "$GEM_DIR" metadata.gz data.tar.gz checksums.yaml.gz
+rm -r "$GEM_DIR"
+CRATE_DIR="$(mktemp -d)"
+mkdir "$CRATE_DIR/src"
+printf '[package]\nname = "%s"\nversion = "1.0.0"\nedition = "2021"\n' "${FILE%.*}" > "$CRATE_DIR/Cargo.toml"
+printf 'description = "xmemhash test package"\nlicense = "MIT"\n' >> "$CRATE_DIR/Cargo.toml"
+echo 'fn main() {}' > "$CRATE_DIR/src/main.rs"
+cp "$FILE" "$CRATE_DIR"
+cargo package --quiet --no-verify --allow-dirty --manifest-path "$CRATE_DIR/Cargo.toml"
+mv "$CRATE_DIR/target/package/$FILE_CRATE" "$FILE_CRATE"
+rm -r "$CRATE_DIR"
+# An OCI image layout, whose image is listed in a nested index (as multi-platform images
+# are), and one whose indices are nested too deeply to follow.  Blobs are stored by their
+# digest, and referred to by a descriptor
+OCI_DIR="$(mktemp -d)"
+mkdir -p "$OCI_DIR/blobs/sha256"
+oci_blob() {
+    cat > "$OCI_DIR/blob"
+    local DIGEST="$(sha256sum "$OCI_DIR/blob" | cut -d ' ' -f 1)"
+    local SIZE="$(wc -c < "$OCI_DIR/blob")"
+    mv "$OCI_DIR/blob" "$OCI_DIR/blobs/sha256/$DIGEST"
+    printf '{"mediaType":"application/vnd.oci.image.%s","digest":"sha256:%s","size":%d}' "$1" "$DIGEST" "$SIZE"
+}
+OCI_LAYER="$(tar -cf - "$FILE" | gzip -n | oci_blob layer.v1.tar+gzip)"
+OCI_CONFIG="$(echo '{}' | oci_blob config.v1+json)"
+OCI_INDEX="$(printf '{"schemaVersion":2,"config":%s,"layers":[%s]}' "$OCI_CONFIG" "$OCI_LAYER" | oci_blob manifest.v1+json)"
+OCI_INDEX="$(printf '{"schemaVersion":2,"manifests":[%s]}' "$OCI_INDEX" | oci_blob index.v1+json)"
+printf '{"imageLayoutVersion":"1.0.0"}' > "$OCI_DIR/oci-layout"
+printf '{"schemaVersion":2,"manifests":[%s]}' "$OCI_INDEX" > "$OCI_DIR/index.json"
+tar -cf "$FILE_OCI" -C "$OCI_DIR" oci-layout index.json blobs
+for _ in $(seq 1 8); do
+    OCI_INDEX="$(printf '{"schemaVersion":2,"manifests":[%s]}' "$OCI_INDEX" | oci_blob index.v1+json)"
+done
+printf '{"schemaVersion":2,"manifests":[%s]}' "$OCI_INDEX" > "$OCI_DIR/index.json"
+tar -cf "$FILE_OCI_DEEP" -C "$OCI_DIR" oci-layout index.json blobs
+rm -r "$OCI_DIR"
+# Nested archives, some of which are too large to keep in memory with a small buffer
+# limit, so are hashed but not expanded
+zip -q "$FILE_NESTED_ZIP" "$FILE" "$FILE_LZW" "$FILE_ZIP" "$FILE_TAR_XZ" "$FILE_LZW_ZIP" "$FILE_GZ_MEMBERS"
 
 echo -n "MD5:    "; md5sum "$FILE"
 echo -n "SHA1:   "; sha1sum "$FILE"
 echo -n "SHA256: "; sha256sum "$FILE"
+echo -n "MD5:    "; md5sum "$FILE_LZW"
+echo -n "SHA1:   "; sha1sum "$FILE_LZW"
+echo -n "SHA256: "; sha256sum "$FILE_LZW"
 
 ./xmemhash --hash "$HASH" "$FILE_ZIP"
 ./xmemhash --hash "$HASH" "$FILE_ZIP_P"
 ./xmemhash --hash "$HASH" "$FILE_7Z"
 ./xmemhash --hash "$HASH" "$FILE_7Z_P"
+./xmemhash --hash "$HASH" "$FILE_ZST"
+./xmemhash --hash "$HASH" "$FILE_TAR_ZST"
+./xmemhash --hash "$HASH" "$FILE_LZ4"
+./xmemhash --hash "$HASH" "$FILE_TAR_LZ4"
+./xmemhash --hash "$HASH" "$FILE_Z"
+./xmemhash --hash "$HASH" "$FILE_TAR_Z"
+./xmemhash --hash "$HASH" "$FILE_LZW_Z"
+./xmemhash --hash "$HASH" "$FILE_LZW_Z12"
+./xmemhash --hash "$HASH" "$FILE_AR"
+./xmemhash --hash "$HASH" -rr "$FILE_DEB"
+./xmemhash --hash "$HASH" "$FILE_CPIO"
+./xmemhash --hash "$HASH" -rr "$FILE_CPIO_XZ"
+./xmemhash --hash "$HASH" "$FILE_RPM"
+# Files are verified against the header even when too large to keep in memory
+./xmemhash --hash "$HASH" --buffer-limit 1 "$FILE_RPM_TAMPERED"
+./xmemhash --hash "$HASH" "$FILE_CAB"
+./xmemhash --hash "$HASH" "$FILE_CAB_Z"
+./xmemhash --hash "$HASH" "$FILE_CAB_LZX"
+./xmemhash --hash "$HASH" "$FILE_EML"
+./xmemhash --hash "$HASH" -rr "$FILE_EML_ZIP"
+./xmemhash --hash "$HASH" "$FILE_RAR"
+./xmemhash --hash "$HASH" "$FILE_RAR_M5"
+./xmemhash --hash "$HASH" "$FILE_RAR_SOLID"
+./xmemhash --hash "$HASH" "$FILE_RAR_E8"
+./xmemhash --hash "$HASH" "$FILE_RAR_DELTA"
+./xmemhash --hash "$HASH" "$FILE_RAR_P"
+./xmemhash --hash "$HASH" "$FILE_RAR_HP"
+./xmemhash --hash "$HASH" "${FILE_RAR_VOL%.rar}.part1.rar"
+./xmemhash --hash "$HASH" "$FILE_XZ"
+./xmemhash --hash "$HASH" "$FILE_TAR_XZ"
+./xmemhash --hash "$HASH" "$FILE_CPIO_ODC"
+./xmemhash --hash "$HASH" "$FILE_ISO"
+./xmemhash --hash "$HASH" "$FILE_ISO_JR"
+./xmemhash --hash "$HASH" -rr "$FILE_MSI"
+./xmemhash --hash "$HASH" "$FILE_JAR"
+./xmemhash --hash "$HASH" "$FILE_EPUB"
+./xmemhash --hash "$HASH" "$FILE_SFX"
+./xmemhash --hash "$HASH" "$FILE_SFX_A"
+./xmemhash --hash "$HASH" "$FILE_LZW_ZIP.001"
+./xmemhash --hash "$HASH" "${FILE_SPLIT_ZIP%.zip}.z01"
+./xmemhash --hash "$HASH" "$FILE_SPLIT_ZIP"
+./xmemhash --hash "$HASH" "$FILE_MSG"
+./xmemhash --hash "$HASH" "$FILE_PDF"
+./xmemhash --hash "$HASH" "$FILE_IMAGE"
+./xmemhash --hash "$HASH" --squash "$FILE_IMAGE"
+./xmemhash --hash "$HASH" "$FILE_NPM"
+./xmemhash --hash "$HASH" "$FILE_WHEEL"
+./xmemhash --hash "$HASH" "$FILE_RAR4"
+./xmemhash --hash "$HASH" -rr "$FILE_RAR4_ZIP"
+./xmemhash --hash "$HASH" "$FILE_GZ_RENAMED"
+./xmemhash --hash "$HASH" --gzip-members "$FILE_GZ_MEMBERS"
+./xmemhash --hash "$HASH" "$FILE_BR"
+./xmemhash --hash "$HASH" "$FILE_TAR_BR"
+./xmemhash --hash "$HASH" -rr "$FILE_GEM"
+./xmemhash --hash "$HASH" -rr "$FILE_GEM_TAMPERED"
+./xmemhash --hash "$HASH" "$FILE_CRATE"
+./xmemhash --hash "$HASH" "$FILE_OCI"
+./xmemhash --hash "$HASH" "$FILE_OCI_DEEP"
+./xmemhash --hash "$HASH" --buffer-limit 64K -rr "$FILE_NESTED_ZIP" 2>&1 \
+    | grep -F "too large to keep in memory" \
+    || echo "[FAIL] Nested archive over the buffer limit was not skipped"
+# Entries are listed in the same order however many threads they are read on
+diff <(./xmemhash --hash "$HASH" -j 1 -rr "$FILE_NESTED_ZIP" 2>&1) \
+    <(./xmemhash --hash "$HASH" -rr "$FILE_NESTED_ZIP" 2>&1) \
+    || echo "[FAIL] Output differs when read on one thread"
 
 rm "$FILE"
 rm "$FILE_ZIP"
 rm "$FILE_ZIP_P"
 rm "$FILE_7Z"
 rm "$FILE_7Z_P"
+rm "$FILE_ZST"
+rm "$FILE_TAR_ZST"
+rm "$FILE_LZ4"
+rm "$FILE_TAR_LZ4"
+rm "$FILE_Z"
+rm "$FILE_TAR_Z"
+rm "$FILE_LZW"
+rm "$FILE_LZW_Z"
+rm "$FILE_LZW_Z12"
+rm "$FILE_AR"
+rm "$FILE_DEB"
+rm "$FILE_LINK"
+rm "$FILE_CPIO"
+rm "$FILE_CPIO_XZ"
+rm "$FILE_RPM"
+rm "$FILE_RPM_TAMPERED"
+rm "$FILE_CAB"
+rm "$FILE_CAB_Z"
+rm "$FILE_EML"
+rm "$FILE_EML_ZIP"
+rm "$FILE_RAR"
+rm "$FILE_RAR_M5"
+rm "$FILE_RAR_SOLID"
+rm "$FILE_RAR_E8"
+rm "$FILE_RAR_DELTA"
+rm "$FILE_RAR_P"
+rm "$FILE_RAR_HP"
+rm "${FILE_RAR_VOL%.rar}".part*.rar
+rm "$FILE_XZ"
+rm "$FILE_TAR_XZ"
+rm "$FILE_SYMLINK"
+rm "$FILE_CPIO_ODC"
+rm "$FILE_ISO"
+rm "$FILE_ISO_JR"
+rm "$FILE_MSI"
+rm "$FILE_JAR"
+rm "$FILE_EPUB"
+rm "$FILE_SFX"
+rm "$FILE_SFX_A"
+rm "$FILE_LZW_ZIP" "$FILE_LZW_ZIP".[0-9][0-9][0-9]
+rm "$FILE_SPLIT_ZIP" "${FILE_SPLIT_ZIP%.zip}".z[0-9][0-9]
+rm "$FILE_PDF"
+rm "$FILE_IMAGE"
+rm "$FILE_NPM"
+rm "$FILE_WHEEL"
+rm "$FILE_RAR4"
+rm "$FILE_RAR4_ZIP"
+rm "$FILE_GZ_RENAMED"
+rm "$FILE_GZ_MEMBERS"
+rm "$FILE_BR"
+rm "$FILE_TAR_BR"
+rm "$FILE_GEM"
+rm "$FILE_GEM_TAMPERED"
+rm "$FILE_CRATE"
+rm "$FILE_OCI"
+rm "$FILE_OCI_DEEP"
+rm "$FILE_NESTED_ZIP"